@@ -0,0 +1,160 @@
+use crate::{Interval, Point3, Ray};
+
+/// Axis-aligned bounding box, stored as one interval per axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    x: Interval,
+    y: Interval,
+    z: Interval,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self::new(Interval::EMPTY, Interval::EMPTY, Interval::EMPTY);
+    pub const UNIVERSE: Self =
+        Self::new(Interval::UNIVERSE, Interval::UNIVERSE, Interval::UNIVERSE);
+
+    // Flat boxes (e.g. around an axis-aligned triangle) are padded to this thickness
+    const MIN_SIZE: f64 = 1e-4;
+
+    pub const fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Box spanning the two corner points, in any order.
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        let x = Interval::new(a.x().min(b.x()), a.x().max(b.x()));
+        let y = Interval::new(a.y().min(b.y()), a.y().max(b.y()));
+        let z = Interval::new(a.z().min(b.z()), a.z().max(b.z()));
+
+        Self::new(x, y, z).pad_to_minimums()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            Interval::enclosing(&self.x, &other.x),
+            Interval::enclosing(&self.y, &other.y),
+            Interval::enclosing(&self.z, &other.z),
+        )
+    }
+
+    pub fn grow(&self, p: Point3) -> Self {
+        self.union(&Self::new(
+            Interval::new(p.x(), p.x()),
+            Interval::new(p.y(), p.y()),
+            Interval::new(p.z(), p.z()),
+        ))
+    }
+
+    /// Box grown by `delta` along every axis, half of it on either side.
+    pub fn expand(&self, delta: f64) -> Self {
+        Self::new(
+            self.x.expand(delta),
+            self.y.expand(delta),
+            self.z.expand(delta),
+        )
+    }
+
+    pub const fn axis(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        Point3::new(self.x.min(), self.y.min(), self.z.min())
+    }
+
+    pub fn max(&self) -> Point3 {
+        Point3::new(self.x.max(), self.y.max(), self.z.max())
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min() + self.max())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0. || self.y.size() < 0. || self.z.size() < 0.
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+
+        if x > y && x > z {
+            0
+        } else if y > z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+        2. * (x * y + y * z + z * x)
+    }
+
+    /// Slab test, returns the parametric distance at which the ray enters the box.
+    pub fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<f64> {
+        self.clip(ray, ray_t).map(|t| t.min())
+    }
+
+    /// Part of `ray_t` over which the ray is inside the box.
+    pub fn clip(&self, ray: &Ray, ray_t: &Interval) -> Option<Interval> {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let mut t_min = ray_t.min();
+        let mut t_max = ray_t.max();
+
+        for (slab, o, d) in [
+            (&self.x, origin.x(), direction.x()),
+            (&self.y, origin.y(), direction.y()),
+            (&self.z, origin.z(), direction.z()),
+        ] {
+            let inv_d = 1. / d;
+            let mut t0 = (slab.min() - o) * inv_d;
+            let mut t1 = (slab.max() - o) * inv_d;
+
+            if inv_d < 0. {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // Written so that NaNs (0 * inf) never shrink the interval
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some(Interval::new(t_min, t_max))
+    }
+
+    fn pad_to_minimums(self) -> Self {
+        let pad = |i: Interval| {
+            if i.size() < Self::MIN_SIZE {
+                i.expand(Self::MIN_SIZE)
+            } else {
+                i
+            }
+        };
+
+        Self::new(pad(self.x), pad(self.y), pad(self.z))
+    }
+}
@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+use crate::{luminance, Color};
+
+/// Sample count chosen per pixel: pixels keep being sampled until the relative standard
+/// error of their mean luminance falls below `threshold`, with at least `min_samples`
+/// and at most `max_samples` samples.
+///
+/// The samples come out of the same budget as without it, `samples_per_pixel` per pixel
+/// on average: after their `min_samples`, the samples flat pixels don't need go to the
+/// noisiest ones, see [`crate::Camera::render_film`]. The render ends early once every
+/// pixel is below the threshold or at `max_samples`. `min_samples` is taken as at least
+/// 2, since the noise of a single sample is unknown, and is taken even past the budget.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveSampling {
+    pub min_samples: i64,
+    pub max_samples: i64,
+    pub threshold: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            min_samples: 16,
+            max_samples: 1024,
+            threshold: 0.02,
+        }
+    }
+}
+
+impl AdaptiveSampling {
+    pub fn converged(&self, stats: &RunningStats) -> bool {
+        stats.count >= self.min_samples.max(2) && stats.relative_error() < self.threshold
+    }
+}
+
+/// Running mean and variance of the luminance of a pixel's samples, with Welford's
+/// algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunningStats {
+    pub count: i64,
+    pub mean: f64,
+    /// Sum of squared differences from the mean.
+    pub m2: f64,
+}
+
+impl RunningStats {
+    pub fn push(&mut self, color: Color) {
+        let x = luminance(color);
+        self.count += 1;
+
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Unbiased sample variance.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// Standard error of the mean relative to the mean, with a small floor on the mean
+    /// so that nearly black pixels don't need an exact zero.
+    pub fn relative_error(&self) -> f64 {
+        const MIN_MEAN: f64 = 1e-3;
+        (self.variance() / self.count.max(1) as f64).sqrt() / self.mean.abs().max(MIN_MEAN)
+    }
+}
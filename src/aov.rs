@@ -0,0 +1,188 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+use serde::Deserialize;
+
+use crate::{splitmix64, write_color, Color, HitRecord, Point3, Reflect, Result, SurfaceId, Vec3};
+
+/// Auxiliary pass rendered alongside the beauty image, mostly from the first surface
+/// each camera ray hits. Averaged passes are averaged over the samples that hit
+/// something, pixels whose rays all miss are 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aov {
+    /// Distance from the camera along its viewing direction.
+    Depth,
+    /// World space shading normal.
+    Normal,
+    /// Surface colour, without lighting.
+    Albedo,
+    /// World space position.
+    Position,
+    /// [`SurfaceId::object`], of the first sample that hits something.
+    ObjectId,
+    /// [`SurfaceId::material`], of the first sample that hits something.
+    MaterialId,
+    /// Number of samples taken for the pixel, which varies with [`crate::AdaptiveSampling`].
+    SampleCount,
+}
+
+impl Aov {
+    const NAMES: [(&'static str, Self); 7] = [
+        ("depth", Self::Depth),
+        ("normal", Self::Normal),
+        ("albedo", Self::Albedo),
+        ("position", Self::Position),
+        ("object_id", Self::ObjectId),
+        ("material_id", Self::MaterialId),
+        ("sample_count", Self::SampleCount),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        let (name, _) = Self::NAMES.iter().find(|(_, aov)| aov == self).unwrap();
+        name
+    }
+
+    /// OpenEXR channels of the pass, a pass with a single channel repeats it in all
+    /// three channels of its image.
+    pub const fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::SampleCount => &["count"],
+        }
+    }
+
+    /// Whether the pass is the average over all samples of a pixel rather than the value
+    /// of a single one, as averaging identifiers is meaningless.
+    pub const fn is_averaged(&self) -> bool {
+        !matches!(self, Aov::ObjectId | Aov::MaterialId | Aov::SampleCount)
+    }
+
+    /// Displayable version of the pass: depth and position are scaled to the range of the
+    /// image, normals mapped from [-1, 1], identifiers given distinct colours and sample
+    /// counts shown as a heat map from blue (fewest) to red (most).
+    pub fn to_rgb8(&self, image: &Rgb32FImage) -> RgbImage {
+        let to_u8 = |c: f64| (255. * c.clamp(0., 1.) + 0.5) as u8;
+
+        match self {
+            Aov::Depth | Aov::Position => {
+                let (min, max) = image
+                    .iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &c| {
+                        (min.min(c), max.max(c))
+                    });
+                let scale = if max > min { 1. / (max - min) } else { 0. };
+
+                ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    Rgb(image
+                        .get_pixel(x, y)
+                        .0
+                        .map(|c| to_u8(((c - min) * scale) as f64)))
+                })
+            }
+            Aov::Normal => ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                Rgb(image
+                    .get_pixel(x, y)
+                    .0
+                    .map(|c| to_u8(0.5 * (c as f64 + 1.))))
+            }),
+            Aov::Albedo => ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                let [r, g, b] = image.get_pixel(x, y).0;
+                write_color(Color::new(r as f64, g as f64, b as f64))
+            }),
+            Aov::ObjectId | Aov::MaterialId => {
+                ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    id_color(image.get_pixel(x, y).0[0] as u32)
+                })
+            }
+            Aov::SampleCount => {
+                let (min, max) = image
+                    .pixels()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| {
+                        (min.min(p.0[0]), max.max(p.0[0]))
+                    });
+                let scale = if max > min { 1. / (max - min) } else { 0. };
+
+                ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+                    let t = ((image.get_pixel(x, y).0[0] - min) * scale) as f64;
+                    Rgb(heat_color(t).map(to_u8))
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for Aov {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::NAMES.iter().find(|(name, _)| *name == s) {
+            Some((_, aov)) => Ok(*aov),
+            None => {
+                let names: Vec<_> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+                bail!("unknown AOV `{}`, expected one of: {}", s, names.join(", "))
+            }
+        }
+    }
+}
+
+/// Everything [`crate::Camera::render`] produces, linear radiance and the requested AOVs.
+#[derive(Debug, Clone)]
+pub struct Passes {
+    pub beauty: Rgb32FImage,
+    pub aovs: Vec<(Aov, Rgb32FImage)>,
+}
+
+impl Passes {
+    pub fn aov(&self, aov: Aov) -> Option<&Rgb32FImage> {
+        self.aovs
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, image)| image)
+    }
+}
+
+/// First thing a camera ray interacts with, a surface or a point in a medium or the fog,
+/// everything the AOV passes are made from.
+#[derive(Debug, Clone, Copy)]
+pub struct AovSample {
+    pub position: Point3,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub id: SurfaceId,
+}
+
+impl AovSample {
+    pub fn new(rec: &HitRecord) -> Self {
+        Self {
+            position: rec.p,
+            normal: rec.normal,
+            albedo: rec.material.albedo(rec),
+            id: rec.id,
+        }
+    }
+}
+
+/// Blue, cyan, green, yellow and red for `t` going from 0 to 1.
+fn heat_color(t: f64) -> [f64; 3] {
+    let t = 4. * t.clamp(0., 1.);
+    [
+        (t - 2.).clamp(0., 1.),
+        if t < 3. { t.min(1.) } else { 4. - t },
+        (2. - t).clamp(0., 1.),
+    ]
+}
+
+/// Arbitrary but stable colour for an identifier, black for 0.
+fn id_color(id: u32) -> Rgb<u8> {
+    if id == 0 {
+        return Rgb([0, 0, 0]);
+    }
+
+    let bytes = splitmix64(id.into()).to_le_bytes();
+    Rgb([bytes[0], bytes[1], bytes[2]])
+}
@@ -0,0 +1,24 @@
+use crate::{Color, Ray};
+
+/// Radiance seen by rays that escape the scene.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Background {
+    /// White to light blue vertical gradient.
+    #[default]
+    Sky,
+    /// Uniform colour, black for scenes lit only by their emissive materials.
+    Solid(Color),
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let unit_dir = ray.direction().unit();
+                let a = 0.5 * (unit_dir.y() + 1.);
+                (1. - a) * Color::new(1., 1., 1.) + a * Color::new(0.5, 0.7, 1.)
+            }
+            Background::Solid(color) => *color,
+        }
+    }
+}
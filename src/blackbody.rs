@@ -0,0 +1,53 @@
+use crate::Color;
+
+/// Linear sRGB colour of the light given off by a black body at `temperature` kelvins,
+/// scaled so that its spectrum peaks at 1, as in pbrt: about white at 6500 K, orange
+/// around 2500 K, dim red around 1500 K, and black at 0 K or less.
+pub fn blackbody(temperature: f64) -> Color {
+    if temperature <= 0. {
+        return Color::ZERO;
+    }
+
+    // Wien's displacement law
+    let peak = planck(2.897_771_955e-3 / temperature * 1e9, temperature);
+
+    let (mut xyz, mut y_integral) = (Color::ZERO, 0.);
+    for lambda in (360..=830).step_by(5).map(f64::from) {
+        let [x, y, z] = cie_xyz(lambda);
+        xyz += planck(lambda, temperature) / peak * Color::new(x, y, z);
+        y_integral += y;
+    }
+    let [x, y, z] = [xyz.x(), xyz.y(), xyz.z()].map(|c| c / y_integral);
+
+    // Colours too saturated for sRGB come out with negative components
+    let r = 3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z;
+    let g = -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z;
+    let b = 0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z;
+    Color::new(r.max(0.), g.max(0.), b.max(0.))
+}
+
+/// Spectral radiance of a black body at `temperature` kelvins, at the wavelength `lambda`
+/// in nanometres.
+fn planck(lambda: f64, temperature: f64) -> f64 {
+    const C: f64 = 299_792_458.;
+    const H: f64 = 6.626_070_15e-34;
+    const KB: f64 = 1.380_649e-23;
+
+    let l = lambda * 1e-9;
+    2. * H * C * C / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.))
+}
+
+/// CIE 1931 colour matching functions at `lambda` nanometres, by the multi-lobe fit of
+/// Wyman, Sloan and Shirley (2013).
+fn cie_xyz(lambda: f64) -> [f64; 3] {
+    let g = |mu: f64, below: f64, above: f64| {
+        let sigma = if lambda < mu { below } else { above };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
@@ -0,0 +1,353 @@
+use crate::{
+    Aabb, HitRecord, HitResult, Hittable, HittableObj, Interval, Point3, Ray, Vec3, INFINITY,
+};
+
+/// Bounding volume hierarchy built with a binned surface area heuristic.
+///
+/// Nodes are stored flat in depth-first order: the first child of an interior
+/// node directly follows it, the second child is referenced by index. Objects
+/// are reordered so that every leaf covers a contiguous range of them.
+///
+/// A BVH is itself a [`Hittable`], so that one built over a mesh can be shared by any
+/// number of [`crate::Instance`]s in a top-level BVH. It is then sampled as a light
+/// through the emissive objects it holds.
+pub struct Bvh<T = HittableObj> {
+    objects: Vec<T>,
+    /// Index of each object of `objects` in the list given to [`Bvh::new`].
+    order: Vec<usize>,
+    nodes: Vec<BvhNode>,
+    /// Indices of the objects of `objects` that give off light.
+    lights: Vec<usize>,
+}
+
+/// Work done by one traversal of a [`Bvh`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraversalStats {
+    /// Nodes whose bounding box was tested.
+    pub nodes: u32,
+    /// Objects tested in the leaves.
+    pub objects: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bbox: Aabb,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    Leaf { first: usize, count: usize },
+    Interior { second_child: usize, axis: usize },
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bbox: Aabb,
+    count: usize,
+}
+
+struct Split {
+    axis: usize,
+    bin: usize,
+    cost: f64,
+}
+
+impl<T: Hittable> Bvh<T> {
+    pub fn new(objects: Vec<T>) -> Self {
+        let boxes: Vec<Aabb> = objects.iter().map(|o| o.bounding_box()).collect();
+        let mut order: Vec<usize> = (0..objects.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+
+        if !objects.is_empty() {
+            BvhNode::build(&boxes, &mut order, 0, &mut nodes);
+        }
+
+        let mut slots: Vec<Option<T>> = objects.into_iter().map(Some).collect();
+        let objects: Vec<T> = order
+            .iter()
+            .map(|&i| {
+                slots[i]
+                    .take()
+                    .expect("every object is placed in exactly one leaf")
+            })
+            .collect();
+        let lights = (0..objects.len())
+            .filter(|&i| objects[i].is_light())
+            .collect();
+
+        Self {
+            objects,
+            order,
+            nodes,
+            lights,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Objects in traversal order, which isn't the order they were given in.
+    pub fn objects(&self) -> &[T] {
+        &self.objects
+    }
+
+    /// Objects in the order they were given to [`Bvh::new`].
+    pub fn into_objects(self) -> Vec<T> {
+        let mut slots: Vec<Option<T>> = (0..self.objects.len()).map(|_| None).collect();
+        for (object, index) in self.objects.into_iter().zip(self.order) {
+            slots[index] = Some(object);
+        }
+
+        slots.into_iter().flatten().collect()
+    }
+
+    /// [`Hittable::hit`] against only the objects for which `include` holds.
+    pub fn hit_where(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        include: impl Fn(&T) -> bool,
+    ) -> HitResult<'_> {
+        self.traverse(ray, ray_t, &mut TraversalStats::default(), include)
+    }
+
+    fn traverse(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        stats: &mut TraversalStats,
+        include: impl Fn(&T) -> bool,
+    ) -> HitResult<'_> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut temp_rec: HitResult<'_> = None;
+        let mut closest_so_far = ray_t.max();
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let node_t = Interval::new(ray_t.min(), closest_so_far);
+            stats.nodes += 1;
+
+            if node.bbox.hit(ray, &node_t).is_none() {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    stats.objects += count as u32;
+                    for hittable in &self.objects[first..first + count] {
+                        if !include(hittable) {
+                            continue;
+                        }
+                        let t = Interval::new(ray_t.min(), closest_so_far);
+                        if let Some(rec) = hittable.hit_with_stats(ray, &t, stats) {
+                            closest_so_far = rec.t;
+                            temp_rec = Some(rec);
+                        }
+                    }
+                }
+                NodeKind::Interior { second_child, axis } => {
+                    // Visit the child nearer to the ray origin first, so the far one can be culled
+                    if ray.direction().axis(axis) < 0. {
+                        stack.push(index + 1);
+                        stack.push(second_child);
+                    } else {
+                        stack.push(second_child);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+
+        temp_rec
+    }
+}
+
+impl BvhNode {
+    const BINS: usize = 16;
+    const MAX_LEAF_SIZE: usize = 4;
+    // Cost of visiting a node, relative to intersecting one object
+    const TRAVERSAL_COST: f64 = 0.125;
+
+    fn build(boxes: &[Aabb], order: &mut [usize], offset: usize, nodes: &mut Vec<BvhNode>) {
+        let bbox = order
+            .iter()
+            .fold(Aabb::EMPTY, |bbox, &i| bbox.union(&boxes[i]));
+        let centroids = order
+            .iter()
+            .fold(Aabb::EMPTY, |bbox, &i| bbox.grow(boxes[i].centroid()));
+
+        let leaf = BvhNode {
+            bbox,
+            kind: NodeKind::Leaf {
+                first: offset,
+                count: order.len(),
+            },
+        };
+
+        if order.len() == 1 {
+            nodes.push(leaf);
+            return;
+        }
+
+        let split = match Self::find_split(boxes, order, &bbox, &centroids) {
+            Some(split) => split,
+            None => {
+                nodes.push(leaf);
+                return;
+            }
+        };
+
+        if order.len() <= Self::MAX_LEAF_SIZE && split.cost >= order.len() as f64 {
+            nodes.push(leaf);
+            return;
+        }
+
+        let axis_bounds = centroids.axis(split.axis);
+        let mut mid = 0;
+        for i in 0..order.len() {
+            let c = boxes[order[i]].centroid().axis(split.axis);
+            if Self::bin_index(c, axis_bounds) < split.bin {
+                order.swap(i, mid);
+                mid += 1;
+            }
+        }
+
+        let index = nodes.len();
+        nodes.push(leaf);
+
+        let (left, right) = order.split_at_mut(mid);
+        Self::build(boxes, left, offset, nodes);
+        let second_child = nodes.len();
+        Self::build(boxes, right, offset + mid, nodes);
+
+        nodes[index].kind = NodeKind::Interior {
+            second_child,
+            axis: split.axis,
+        };
+    }
+
+    fn find_split(boxes: &[Aabb], order: &[usize], bbox: &Aabb, centroids: &Aabb) -> Option<Split> {
+        let parent_area = bbox.surface_area();
+        let mut best: Option<Split> = None;
+
+        for axis in 0..3 {
+            let axis_bounds = centroids.axis(axis);
+            if axis_bounds.size() <= 0. {
+                continue;
+            }
+
+            let mut bins = [Bin {
+                bbox: Aabb::EMPTY,
+                count: 0,
+            }; Self::BINS];
+
+            for &i in order {
+                let bin = &mut bins[Self::bin_index(boxes[i].centroid().axis(axis), axis_bounds)];
+                bin.bbox = bin.bbox.union(&boxes[i]);
+                bin.count += 1;
+            }
+
+            // Sweep from the right, so that right_cost[b] covers bins b..
+            let mut right_cost = [0.; Self::BINS];
+            let mut acc = Bin {
+                bbox: Aabb::EMPTY,
+                count: 0,
+            };
+            for b in (1..Self::BINS).rev() {
+                acc.bbox = acc.bbox.union(&bins[b].bbox);
+                acc.count += bins[b].count;
+                right_cost[b] = acc.bbox.surface_area() * acc.count as f64;
+            }
+
+            let mut left = Bin {
+                bbox: Aabb::EMPTY,
+                count: 0,
+            };
+            for b in 1..Self::BINS {
+                left.bbox = left.bbox.union(&bins[b - 1].bbox);
+                left.count += bins[b - 1].count;
+
+                if left.count == 0 || left.count == order.len() {
+                    continue;
+                }
+
+                let cost = Self::TRAVERSAL_COST
+                    + (left.bbox.surface_area() * left.count as f64 + right_cost[b]) / parent_area;
+
+                if best.as_ref().is_none_or(|best| cost < best.cost) {
+                    best = Some(Split { axis, bin: b, cost });
+                }
+            }
+        }
+
+        best
+    }
+
+    fn bin_index(centroid: f64, bounds: &Interval) -> usize {
+        let relative = (centroid - bounds.min()) / bounds.size();
+        ((relative * Self::BINS as f64) as usize).min(Self::BINS - 1)
+    }
+}
+
+impl<T: Hittable> Hittable for Bvh<T> {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        self.hit_with_stats(ray, ray_t, &mut TraversalStats::default())
+    }
+
+    fn hit_with_stats(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        stats: &mut TraversalStats,
+    ) -> HitResult<'_> {
+        self.traverse(ray, ray_t, stats, |_| true)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |node| node.bbox)
+    }
+
+    fn is_light(&self) -> bool {
+        !self.lights.is_empty()
+    }
+
+    // An emissive object is picked uniformly, then a point on it. The density is the one
+    // of the object seen first along the ray: the others behind it are left out, as their
+    // samples are hidden by it, see `World::light_pdf`.
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        if self.lights.is_empty() {
+            return 0.;
+        }
+
+        match self.hit(ray, &Interval::new(0.001, INFINITY)) {
+            Some(HitRecord {
+                object: Some(object),
+                ..
+            }) if object.is_light() => object.pdf_value(ray) / self.lights.len() as f64,
+            _ => 0.,
+        }
+    }
+
+    fn random(&self, origin: Point3, time: f64, (u, v): (f64, f64)) -> Vec3 {
+        if self.lights.is_empty() {
+            return Vec3::X;
+        }
+
+        // The part of `u` left over from picking the object is uniform again
+        let scaled = u * self.lights.len() as f64;
+        let index = (scaled as usize).min(self.lights.len() - 1);
+
+        self.objects[self.lights[index]].random(origin, time, (scaled - index as f64, v))
+    }
+}
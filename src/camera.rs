@@ -1,8 +1,22 @@
-use crate::{progress_bar, write_color, Color, Image, Point3, Ray, Result, Vec3, World};
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use image::{ImageBuffer, RgbImage};
-use indicatif::ProgressBar;
-use rand::{rngs::ThreadRng, thread_rng, Rng};
+use crate::{
+    progress_bar, AdaptiveSampling, Aov, AovSample, Background, Denoiser, Film, FilmPixel, Filter,
+    Image, Integrator, Passes, PathIntegrator, Point3, Ray, Result, Sampler, SamplerKind,
+    SplatTile, Tile, Vec3, World,
+};
+
+use anyhow::bail;
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use rand::{thread_rng, Rng};
 
 pub struct Camera {
     image_width: i64,
@@ -11,19 +25,34 @@ pub struct Camera {
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
-    max_depth: u16,
+    integrator: Arc<dyn Integrator>,
     lookfrom: Point3,
+    forward: Vec3,
     defocus_angle: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    shutter_open: f64,
+    shutter_close: f64,
+    threads: usize,
+    tile_size: u32,
+    seed: Option<u64>,
+    background: Background,
+    aovs: Vec<Aov>,
+    denoiser: Option<Denoiser>,
+    adaptive: Option<AdaptiveSampling>,
+    sampler: SamplerKind,
+    filter: Filter,
+    samples_per_pass: Option<i64>,
+    time_budget: Option<Duration>,
     pb: ProgressBar,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         img: Image,
         samples_per_pixel: i64,
-        max_depth: u16,
+        max_depth: Option<u16>,
         vfov: f64,
         lookfrom: Point3,
         lookat: Point3,
@@ -44,10 +73,11 @@ impl Camera {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn initialize(
         img: Image,
         samples_per_pixel: i64,
-        max_depth: u16,
+        max_depth: Option<u16>,
         vfov: f64,
         lookfrom: Point3,
         lookat: Point3,
@@ -70,7 +100,7 @@ impl Camera {
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges
         let viewport_u = viewport_width * u;
-        let viewport_v = viewport_width * -v;
+        let viewport_v = viewport_height * -v;
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel
         let pixel_delta_u = viewport_u / img.width() as f64;
@@ -90,65 +120,456 @@ impl Camera {
             pixel_delta_u,
             pixel_delta_v,
             samples_per_pixel,
-            max_depth,
+            integrator: Arc::new(PathIntegrator::new(max_depth)),
             lookfrom,
+            forward: -w,
             defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            shutter_open: 0.,
+            shutter_close: 0.,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            tile_size: Tile::DEFAULT_SIZE,
+            seed: None,
+            background: Background::default(),
+            aovs: vec![],
+            denoiser: None,
+            adaptive: None,
+            sampler: SamplerKind::default(),
+            filter: Filter::default(),
+            samples_per_pass: None,
+            time_budget: None,
             pb,
             image_width: img.width().into(),
             image_height: img.height().into(),
         }
     }
 
-    pub fn render(&self, world: World) -> Result<RgbImage> {
-        let mut img_buffer: RgbImage =
-            ImageBuffer::new(self.image_width.try_into()?, self.image_height.try_into()?);
+    /// Number of worker threads used by [`Camera::render`], defaults to the available parallelism.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Edge length in pixels of the square tiles handed out to the workers.
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
 
-        let mut rng = thread_rng();
+    /// Seeds the per-pixel random number generators, so that renders of the same scene are
+    /// bit-identical whatever the thread count. Without a seed every render draws a fresh one.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
 
-        for (x, y, pixel) in img_buffer.enumerate_pixels_mut() {
-            let mut pixel_color = Color::ZERO;
+    /// What rays that leave the scene see, the sky gradient by default.
+    pub fn with_background(mut self, background: Background) -> Self {
+        self.background = background;
+        self
+    }
 
-            for _ in 0..self.samples_per_pixel {
-                let ray = Self::get_ray(self, &mut rng, x.into(), y.into());
-                pixel_color += ray.color(&mut rng, self.max_depth, &world);
+    /// Auxiliary passes rendered along with the beauty image, none by default.
+    pub fn with_aovs(mut self, aovs: impl IntoIterator<Item = Aov>) -> Self {
+        self.aovs.clear();
+        for aov in aovs {
+            if !self.aovs.contains(&aov) {
+                self.aovs.push(aov);
             }
+        }
+        self
+    }
+
+    /// Denoises the beauty image once rendered, off by default. The passes the denoiser
+    /// needs are rendered whether they were asked for with [`Camera::with_aovs`] or not.
+    pub fn with_denoiser(mut self, denoiser: Option<Denoiser>) -> Self {
+        self.denoiser = denoiser;
+        self
+    }
+
+    /// Spreads the samples, `samples_per_pixel` per pixel on average, by the noise of each
+    /// pixel instead of giving every pixel as many, off by default.
+    pub fn with_adaptive_sampling(mut self, adaptive: Option<AdaptiveSampling>) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    /// How the random numbers of the samples of a pixel are spread out, Owen-scrambled Sobol
+    /// points by default.
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Reconstruction filter the samples are splatted to the pixels with, a box of one pixel
+    /// by default.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Times the shutter opens and closes at, every ray is given a time in between. Objects
+    /// move from time 0 to time 1, see [`crate::Sphere::moving`] and
+    /// [`crate::AnimatedTransform`]. An instant at time 0 by default, without motion blur.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close.max(open);
+        self
+    }
+
+    /// Light transport algorithm the samples are shaded with, a [`PathIntegrator`] of at
+    /// most `max_depth` bounces by default.
+    pub fn with_integrator(mut self, integrator: Arc<dyn Integrator>) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Renders in passes of `samples` samples per pixel over the whole image, so that it
+    /// can be stopped and previewed early, instead of all samples of a tile at once.
+    pub fn with_samples_per_pass(mut self, samples: i64) -> Self {
+        self.samples_per_pass = Some(samples.max(1));
+        self
+    }
 
-            self.pb.inc(1);
+    /// Stops rendering after the first pass that ends past `budget`, see
+    /// [`Camera::render_film`].
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
 
-            *pixel = write_color(pixel_color, self.samples_per_pixel);
+    /// Shows or hides the progress bar, which is drawn to stderr by default.
+    pub fn with_progress(self, show: bool) -> Self {
+        if !show {
+            self.pb.set_draw_target(ProgressDrawTarget::hidden());
         }
+        self
+    }
+
+    /// Renders the average linear radiance reaching each pixel and the requested AOVs,
+    /// see [`crate::save_passes`] to write them out.
+    pub fn render(&self, world: World) -> Result<Passes> {
+        let mut film = self.film()?;
+        self.render_film(&world, &mut film, |_| Ok(()))?;
+        self.develop(&film)
+    }
 
-        self.pb.finish_with_message("finished rendering image");
+    /// Empty film for this camera, to render into with [`Camera::render_film`].
+    pub fn film(&self) -> Result<Film> {
+        let mut aovs = self.aovs.clone();
+        if self.denoiser.is_some() {
+            for feature in Denoiser::FEATURES {
+                if !aovs.contains(&feature) {
+                    aovs.push(feature);
+                }
+            }
+        }
 
-        Ok(img_buffer)
+        Ok(Film::new(
+            self.image_width.try_into()?,
+            self.image_height.try_into()?,
+            self.seed.unwrap_or_else(|| thread_rng().gen()),
+            self.settings(),
+            aovs,
+        ))
     }
 
-    fn get_ray(&self, rng: &mut ThreadRng, i: i64, j: i64) -> Ray {
-        let pixel_center =
-            self.pixel00_loc + (i as f64 * self.pixel_delta_u) + (j as f64 * self.pixel_delta_v);
-        let pixel_sample = pixel_center + Self::pixel_sample_square(self, rng);
+    /// Settings the samples depend on, beyond the seed and the scene, which a film has to
+    /// be resumed with.
+    fn settings(&self) -> String {
+        format!(
+            "{:?}, {:?} sampler, {} samples per pixel, adaptive sampling {:?}, {:?}",
+            self.filter, self.sampler, self.samples_per_pixel, self.adaptive, self.integrator
+        )
+    }
+
+    /// Adds samples to `film` in passes over the whole image, each adding up to
+    /// [`Camera::with_samples_per_pass`] samples to every pixel, until all pixels have
+    /// their samples or the time budget runs out. With adaptive sampling, passes first bring
+    /// every pixel to its `min_samples`, then share what is left of the budget between the
+    /// pixels still above the noise threshold, noisiest first. `on_pass` is called after
+    /// every pass, e.g. to save the film or a preview.
+    ///
+    /// The film can come from an interrupted render of the same scene, the finished image
+    /// is then the same as if the render had never stopped. It must have been rendered with
+    /// the same filter, sampler, sample counts and integrator. Returns whether the render is
+    /// complete.
+    pub fn render_film(
+        &self,
+        world: &World,
+        film: &mut Film,
+        mut on_pass: impl FnMut(&Film) -> Result<()>,
+    ) -> Result<bool> {
+        if (i64::from(film.width()), i64::from(film.height()))
+            != (self.image_width, self.image_height)
+        {
+            bail!(
+                "the film is {}x{} but the camera renders {}x{} images",
+                film.width(),
+                film.height(),
+                self.image_width,
+                self.image_height
+            );
+        }
+        if let Some(adaptive) = self
+            .adaptive
+            .filter(|adaptive| adaptive.min_samples > adaptive.max_samples)
+        {
+            bail!(
+                "adaptive sampling has more min_samples ({}) than max_samples ({})",
+                adaptive.min_samples,
+                adaptive.max_samples
+            );
+        }
+        if film.settings() != self.settings() {
+            bail!(
+                "the film was rendered with other settings:\n  {}\nthan the camera's:\n  {}",
+                film.settings(),
+                self.settings()
+            );
+        }
+        if let Some(aov) = self
+            .film()?
+            .aovs()
+            .iter()
+            .find(|aov| !film.aovs().contains(aov))
+        {
+            bail!("the film has no {} pass", aov.name());
+        }
+
+        let start = Instant::now();
+        let tiles = Tile::grid(film.width(), film.height(), self.tile_size);
+        let pixel_count = film.width() as u64 * film.height() as u64;
+
+        let min_samples = self.adaptive.map_or(0, |adaptive| adaptive.min_samples);
+        self.pb
+            .set_length(pixel_count * self.samples_per_pixel.max(min_samples).max(0) as u64);
+        self.pb.set_position(film.samples());
+
+        loop {
+            let next_tile = AtomicUsize::new(0);
+            let (sender, receiver) = mpsc::channel();
+            let mut rendered = vec![];
+            let targets = self.pass_targets(film);
+
+            thread::scope(|scope| {
+                for _ in 0..self.threads.min(tiles.len()) {
+                    let sender = sender.clone();
+                    let (tiles, next_tile, film, targets) = (&tiles, &next_tile, &*film, &targets);
+
+                    scope.spawn(move || {
+                        while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+                        {
+                            let (pixels, splats) = self.render_tile(film, tile, targets, world);
+
+                            if sender.send((*tile, pixels, splats)).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                }
+                drop(sender);
+
+                rendered.extend(receiver);
+            });
+
+            // Splats overlap neighbouring tiles, adding them up in a fixed order keeps the
+            // image independent of the thread count
+            rendered.sort_by_key(|(tile, _, _)| (tile.y, tile.x));
+
+            let before = film.samples();
+            for (tile, pixels, splats) in rendered {
+                film.set_tile(&tile, pixels);
+                film.add_splats(&splats);
+            }
+            if film.samples() == before {
+                self.pb.finish_with_message("finished rendering image");
+                return Ok(true);
+            }
+
+            on_pass(film)?;
+
+            if self
+                .time_budget
+                .is_some_and(|budget| start.elapsed() >= budget)
+            {
+                self.pb
+                    .abandon_with_message("stopped rendering at the time budget");
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Averages the samples of `film` into the beauty image and the requested AOVs,
+    /// denoising the image if enabled.
+    pub fn develop(&self, film: &Film) -> Result<Passes> {
+        let mut passes = film.passes();
+
+        if let Some(denoiser) = &self.denoiser {
+            passes.beauty = denoiser.denoise(&passes)?;
+        }
+        passes.aovs.retain(|(aov, _)| self.aovs.contains(aov));
+
+        Ok(passes)
+    }
+
+    /// Most samples a pixel can get.
+    fn max_samples(&self) -> i64 {
+        self.adaptive
+            .map_or(self.samples_per_pixel, |adaptive| adaptive.max_samples)
+    }
+
+    /// Sample count every pixel of `film` is brought to by the next pass, in the order of
+    /// [`Film::pixels`].
+    ///
+    /// Without adaptive sampling, every pixel takes up to `samples_per_pass` more samples,
+    /// until it has `samples_per_pixel`. With it, every pixel first takes its `min_samples`,
+    /// then what is left of the budget of `samples_per_pixel` per pixel goes to the pixels
+    /// still above the noise threshold, noisiest first, a pass of samples at a time and up
+    /// to `max_samples` each. Passes default to all the samples of a pixel at once, or to
+    /// `min_samples` for adaptive sampling so that the noise is measured again in between.
+    fn pass_targets(&self, film: &Film) -> Vec<i64> {
+        let pixels = film.pixels();
+
+        let Some(adaptive) = self.adaptive else {
+            let step = self.samples_per_pass.unwrap_or(self.samples_per_pixel);
+            return pixels
+                .iter()
+                .map(|pixel| {
+                    let count = pixel.stats.count;
+                    count.saturating_add(step).min(self.samples_per_pixel)
+                })
+                .collect();
+        };
+
+        let min_samples = adaptive.min_samples.max(2).min(adaptive.max_samples);
+        let step = self.samples_per_pass.unwrap_or(min_samples).max(1);
+        let mut targets: Vec<_> = pixels.iter().map(|pixel| pixel.stats.count).collect();
+
+        if targets.iter().any(|&count| count < min_samples) {
+            for target in &mut targets {
+                *target = target.saturating_add(step).min(min_samples).max(*target);
+            }
+            return targets;
+        }
+
+        let budget = (pixels.len() as i64).saturating_mul(self.samples_per_pixel);
+        let mut left = budget.saturating_sub(film.samples() as i64);
+
+        let mut noisy: Vec<_> = (0..pixels.len())
+            .filter(|&i| {
+                let stats = &pixels[i].stats;
+                stats.count < adaptive.max_samples && !adaptive.converged(stats)
+            })
+            .map(|i| (i, pixels[i].stats.relative_error()))
+            .collect();
+        noisy.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        for (i, _) in noisy {
+            let extra = step.min(adaptive.max_samples - targets[i]).min(left);
+            if extra <= 0 {
+                break;
+            }
+            targets[i] += extra;
+            left -= extra;
+        }
+
+        targets
+    }
+
+    /// Pixels of `tile` in `film` with the samples of the next pass added, up to their
+    /// count in `targets`, and the splats of those samples.
+    fn render_tile(
+        &self,
+        film: &Film,
+        tile: &Tile,
+        targets: &[i64],
+        world: &World,
+    ) -> (Vec<FilmPixel>, SplatTile) {
+        let max_samples = self.max_samples();
+        let mut pixels = film.tile(tile);
+        let mut splats = SplatTile::new(tile, &self.filter, film.width(), film.height());
+        let mut taken = 0;
+
+        for ((x, y), pixel) in tile.pixels().zip(&mut pixels) {
+            let target = targets[y as usize * film.width() as usize + x as usize];
+
+            while pixel.stats.count < target {
+                let sampler = &mut self.sampler.sampler(
+                    film.seed(),
+                    x,
+                    y,
+                    pixel.stats.count as u64,
+                    max_samples as u64,
+                );
+                let (u, v) = sampler.get_2d();
+                let (film_x, film_y) = (f64::from(x) + u, f64::from(y) + v);
+                let ray = self.get_ray(sampler, film_x, film_y);
+                let (color, hit) = self
+                    .integrator
+                    .radiance(&ray, sampler, world, &self.background);
+
+                splats.add(film_x, film_y, color, &self.filter);
+                pixel.stats.push(color);
+                taken += 1;
+
+                if let Some(hit) = hit {
+                    for (value, aov) in pixel.aovs.iter_mut().zip(film.aovs()) {
+                        if aov.is_averaged() {
+                            *value += self.aov_value(*aov, &hit);
+                        } else if pixel.hits == 0 {
+                            *value = self.aov_value(*aov, &hit);
+                        }
+                    }
+                    pixel.hits += 1;
+                }
+            }
+        }
+
+        self.pb.inc(taken);
+
+        (pixels, splats)
+    }
+
+    fn aov_value(&self, aov: Aov, hit: &AovSample) -> Vec3 {
+        match aov {
+            Aov::Depth => Vec3::splat((hit.position - self.lookfrom).dot(self.forward)),
+            Aov::Normal => hit.normal,
+            Aov::Albedo => hit.albedo,
+            Aov::Position => hit.position,
+            Aov::ObjectId => Vec3::splat(hit.id.object as f64),
+            Aov::MaterialId => Vec3::splat(hit.id.material as f64),
+            // Counted per pixel rather than per sample, see `Film::passes`
+            Aov::SampleCount => Vec3::ZERO,
+        }
+    }
+
+    /// Ray through film position `(x, y)`, in pixels from the top left corner of the image,
+    /// at a time within the shutter interval.
+    fn get_ray<S: Sampler + ?Sized>(&self, sampler: &mut S, x: f64, y: f64) -> Ray {
+        let pixel_sample =
+            self.pixel00_loc + ((x - 0.5) * self.pixel_delta_u) + ((y - 0.5) * self.pixel_delta_v);
 
         let ray_origin = if self.defocus_angle <= 0. {
             self.lookfrom
         } else {
-            Self::defocus_disk_sample(self, rng)
+            Self::defocus_disk_sample(self, sampler)
         };
         let ray_direction = pixel_sample - ray_origin;
 
-        Ray::new(ray_origin, ray_direction)
-    }
-
-    fn pixel_sample_square(&self, rng: &mut ThreadRng) -> Vec3 {
-        let px = -0.5 + rng.gen::<f64>();
-        let py = -0.5 + rng.gen::<f64>();
+        // No dimension is drawn for an instant shutter, so still images don't change
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + sampler.get_1d() * (self.shutter_close - self.shutter_open)
+        } else {
+            self.shutter_open
+        };
 
-        (px * self.pixel_delta_u) + (py * self.pixel_delta_v)
+        Ray::new(ray_origin, ray_direction, time)
     }
 
-    fn defocus_disk_sample(&self, rng: &mut ThreadRng) -> Point3 {
-        let p = Vec3::random_in_unit_disk(rng);
+    fn defocus_disk_sample<S: Sampler + ?Sized>(&self, sampler: &mut S) -> Point3 {
+        let p = Vec3::sample_unit_disk(sampler.get_2d());
         self.lookfrom + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
     }
 }
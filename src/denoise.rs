@@ -0,0 +1,151 @@
+use anyhow::anyhow;
+use image::{ImageBuffer, Rgb, Rgb32FImage};
+use serde::Deserialize;
+
+use crate::{Aov, Color, Passes, Result, Vec3};
+
+/// Edge-avoiding à-trous wavelet filter, after Dammertz et al., "Edge-Avoiding À-Trous
+/// Wavelet Transform for fast Global Illumination Filtering" (HPG 2010).
+///
+/// Repeatedly blurs the beauty image with a 5x5 B3 spline kernel whose taps spread twice
+/// as far apart on every pass, weighting each tap down the more its colour, albedo,
+/// normal and depth differ from the centre pixel. Lighting is filtered separately from
+/// the albedo, so that textures stay sharp.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Denoiser {
+    /// Number of filter passes, the last one covers `4 * 2^(iterations - 1)` pixels around
+    /// the centre.
+    pub iterations: u32,
+    /// Colour difference at which a tap is weighted down by `1/e`, halved on every pass.
+    pub color_sigma: f64,
+    pub albedo_sigma: f64,
+    pub normal_sigma: f64,
+    /// Depth difference relative to the depth of the centre pixel.
+    pub depth_sigma: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 8.,
+            albedo_sigma: 0.1,
+            normal_sigma: 0.3,
+            depth_sigma: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Passes the denoiser is guided by.
+    pub const FEATURES: [Aov; 3] = [Aov::Albedo, Aov::Normal, Aov::Depth];
+
+    const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+    /// Filters the beauty image of `passes`, which must include all [`Denoiser::FEATURES`].
+    pub fn denoise(&self, passes: &Passes) -> Result<Rgb32FImage> {
+        let feature = |aov: Aov| {
+            passes
+                .aov(aov)
+                .map(pixels)
+                .ok_or_else(|| anyhow!("the denoiser needs the {} pass", aov.name()))
+        };
+        let albedo = feature(Aov::Albedo)?;
+        let normal = feature(Aov::Normal)?;
+        let depth: Vec<f64> = feature(Aov::Depth)?.iter().map(Vec3::x).collect();
+
+        let (width, height) = passes.beauty.dimensions();
+        let (width, height) = (width as i64, height as i64);
+
+        // Lighting without the surface colour, which is put back once filtered
+        let albedo_or_one = |a: Color| Color::new(or_one(a.x()), or_one(a.y()), or_one(a.z()));
+        let mut irradiance: Vec<Color> = pixels(&passes.beauty)
+            .iter()
+            .zip(&albedo)
+            .map(|(&color, &a)| {
+                let a = albedo_or_one(a);
+                Color::new(color.x() / a.x(), color.y() / a.y(), color.z() / a.z())
+            })
+            .collect();
+
+        let inv_sq = |sigma: f64| 1. / (sigma * sigma).max(1e-12);
+        let albedo_weight = inv_sq(self.albedo_sigma);
+        let normal_weight = inv_sq(self.normal_sigma);
+        let depth_weight = inv_sq(self.depth_sigma);
+
+        for iteration in 0..self.iterations {
+            let step = 1_i64 << iteration;
+            let color_weight = inv_sq(self.color_sigma / step as f64);
+            let mut filtered = Vec::with_capacity(irradiance.len());
+
+            for y in 0..height {
+                for x in 0..width {
+                    let p = (y * width + x) as usize;
+                    let mut sum = Color::ZERO;
+                    let mut total_weight = 0.;
+
+                    for (j, ky) in Self::KERNEL.iter().enumerate() {
+                        let qy = y + (j as i64 - 2) * step;
+                        if !(0..height).contains(&qy) {
+                            continue;
+                        }
+
+                        for (i, kx) in Self::KERNEL.iter().enumerate() {
+                            let qx = x + (i as i64 - 2) * step;
+                            if !(0..width).contains(&qx) {
+                                continue;
+                            }
+                            let q = (qy * width + qx) as usize;
+
+                            let relative_depth =
+                                (depth[p] - depth[q]) / depth[p].abs().max(f64::EPSILON);
+                            let distance = color_weight
+                                * (irradiance[p] - irradiance[q]).length_squared()
+                                + albedo_weight * (albedo[p] - albedo[q]).length_squared()
+                                + normal_weight * (normal[p] - normal[q]).length_squared()
+                                + depth_weight * relative_depth * relative_depth;
+
+                            let weight = kx * ky * (-distance).exp();
+                            sum += weight * irradiance[q];
+                            total_weight += weight;
+                        }
+                    }
+
+                    // The centre tap always has a weight of (3/8)^2
+                    filtered.push(sum / total_weight);
+                }
+            }
+
+            irradiance = filtered;
+        }
+
+        let denoised = irradiance
+            .iter()
+            .zip(&albedo)
+            .flat_map(|(&e, &a)| {
+                let c = e * albedo_or_one(a);
+                [c.x() as f32, c.y() as f32, c.z() as f32]
+            })
+            .collect();
+
+        Ok(ImageBuffer::from_raw(width as u32, height as u32, denoised)
+            .expect("one colour per pixel"))
+    }
+}
+
+fn pixels(image: &Rgb32FImage) -> Vec<Vec3> {
+    image
+        .pixels()
+        .map(|&Rgb([r, g, b])| Vec3::new(r as f64, g as f64, b as f64))
+        .collect()
+}
+
+/// Albedo channels too dark to divide by are left out of the demodulation.
+fn or_one(albedo: f64) -> f64 {
+    if albedo > 1e-3 {
+        albedo
+    } else {
+        1.
+    }
+}
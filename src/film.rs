@@ -0,0 +1,370 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use image::{ImageBuffer, Rgb, Rgb32FImage};
+
+use crate::{Aov, Color, Filter, Passes, Result, RunningStats, Tile};
+
+/// Samples accumulated by a render so far, see [`crate::Camera::render_film`]. A film can be
+/// saved to disk and loaded back to carry on rendering where it stopped.
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    seed: u64,
+    settings: String,
+    aovs: Vec<Aov>,
+    pixels: Vec<FilmPixel>,
+    splats: Vec<Splat>,
+}
+
+/// Running sums of the samples taken in one pixel of a [`Film`].
+#[derive(Debug, Clone)]
+pub struct FilmPixel {
+    /// Per AOV of the film, the sum over the samples that hit something for averaged AOVs,
+    /// or the value of the first one for the others.
+    pub aovs: Vec<Color>,
+    /// Number of samples that hit something, which the averaged AOVs are divided by.
+    pub hits: i64,
+    /// Sample count and luminance statistics.
+    pub stats: RunningStats,
+}
+
+impl FilmPixel {
+    fn new(aovs: usize) -> Self {
+        Self {
+            aovs: vec![Color::ZERO; aovs],
+            hits: 0,
+            stats: RunningStats::default(),
+        }
+    }
+}
+
+/// Radiance of the samples landing around a pixel, weighted by the reconstruction
+/// [`Filter`].
+#[derive(Debug, Clone, Copy)]
+pub struct Splat {
+    pub color: Color,
+    pub weight: f64,
+}
+
+impl Splat {
+    pub const ZERO: Self = Self {
+        color: Color::ZERO,
+        weight: 0.,
+    };
+}
+
+/// Splats of the samples of a tile, over the tile grown by the radius of the filter.
+#[derive(Debug, Clone)]
+pub struct SplatTile {
+    region: Tile,
+    splats: Vec<Splat>,
+}
+
+impl SplatTile {
+    /// Empty splats of the samples of `tile` of a `width` x `height` film.
+    pub fn new(tile: &Tile, filter: &Filter, width: u32, height: u32) -> Self {
+        let margin = (filter.radius() - 0.5).max(0.).ceil() as u32;
+        let region = tile.expand(margin, width, height);
+
+        Self {
+            region,
+            splats: vec![Splat::ZERO; region.pixel_count() as usize],
+        }
+    }
+
+    /// Adds a sample at film position `(x, y)`, in pixels from the top left corner, to the
+    /// pixels around it. Pixels outside the film are skipped.
+    pub fn add(&mut self, x: f64, y: f64, color: Color, filter: &Filter) {
+        let r = filter.radius();
+        let Tile {
+            x: x0,
+            y: y0,
+            width,
+            height,
+        } = self.region;
+
+        // Pixels whose centre `i + 0.5` is at an offset in `[-r, r)` from the sample
+        let range = |p: f64, start: u32, len: u32| {
+            let first = (p - 0.5 - r).floor() as i64 + 1;
+            let last = (p - 0.5 + r).floor() as i64;
+            first.max(start.into())..=last.min(i64::from(start + len) - 1)
+        };
+
+        for j in range(y, y0, height) {
+            let dy = y - (j as f64 + 0.5);
+            for i in range(x, x0, width) {
+                let dx = x - (i as f64 + 0.5);
+                let weight = filter.evaluate(dx, dy);
+
+                let splat = &mut self.splats
+                    [(j - i64::from(y0)) as usize * width as usize + (i - i64::from(x0)) as usize];
+                splat.color += weight * color;
+                splat.weight += weight;
+            }
+        }
+    }
+}
+
+impl Film {
+    const MAGIC: &'static [u8; 8] = b"RTFILM\0\x04";
+
+    /// Empty film, rendered with `seed` and the sampling `settings` of the camera, see
+    /// [`crate::Camera::film`], and recording `aovs`.
+    pub fn new(width: u32, height: u32, seed: u64, settings: String, aovs: Vec<Aov>) -> Self {
+        let pixel = FilmPixel::new(aovs.len());
+
+        Self {
+            width,
+            height,
+            seed,
+            settings,
+            aovs,
+            pixels: vec![pixel; width as usize * height as usize],
+            splats: vec![Splat::ZERO; width as usize * height as usize],
+        }
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn settings(&self) -> &str {
+        &self.settings
+    }
+
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    /// All pixels, row by row from the top left corner.
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> &FilmPixel {
+        &self.pixels[self.index(x, y)]
+    }
+
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> &mut FilmPixel {
+        let index = self.index(x, y);
+        &mut self.pixels[index]
+    }
+
+    pub fn splat(&self, x: u32, y: u32) -> &Splat {
+        &self.splats[self.index(x, y)]
+    }
+
+    /// Copy of the pixels of `tile`, in the order of [`Tile::pixels`].
+    pub fn tile(&self, tile: &Tile) -> Vec<FilmPixel> {
+        tile.pixels()
+            .map(|(x, y)| self.pixel(x, y).clone())
+            .collect()
+    }
+
+    /// Replaces the pixels of `tile` with `pixels`, in the order of [`Tile::pixels`].
+    pub fn set_tile(&mut self, tile: &Tile, pixels: Vec<FilmPixel>) {
+        for ((x, y), pixel) in tile.pixels().zip(pixels) {
+            *self.pixel_mut(x, y) = pixel;
+        }
+    }
+
+    /// Adds the splats of the samples of a tile.
+    pub fn add_splats(&mut self, splats: &SplatTile) {
+        for ((x, y), splat) in splats.region.pixels().zip(&splats.splats) {
+            let index = self.index(x, y);
+            self.splats[index].color += splat.color;
+            self.splats[index].weight += splat.weight;
+        }
+    }
+
+    /// Total number of samples taken over all pixels.
+    pub fn samples(&self) -> u64 {
+        self.pixels.iter().map(|p| p.stats.count as u64).sum()
+    }
+
+    /// Averages the samples into the beauty image and AOVs.
+    pub fn passes(&self) -> Passes {
+        let image = |value: &dyn Fn(u32, u32) -> Color| -> Rgb32FImage {
+            ImageBuffer::from_fn(self.width, self.height, |x, y| {
+                let c = value(x, y);
+                Rgb([c.x() as f32, c.y() as f32, c.z() as f32])
+            })
+        };
+
+        Passes {
+            // Filters with negative lobes can take pixels below 0 around sharp edges
+            beauty: image(&|x, y| match self.splat(x, y) {
+                Splat { color, weight } if *weight != 0. => {
+                    let c = *color / *weight;
+                    Color::new(c.x().max(0.), c.y().max(0.), c.z().max(0.))
+                }
+                _ => Color::ZERO,
+            }),
+            aovs: self
+                .aovs
+                .iter()
+                .enumerate()
+                .map(|(i, aov)| {
+                    let pass = image(&|x, y| match (aov, self.pixel(x, y)) {
+                        (Aov::SampleCount, pixel) => Color::splat(pixel.stats.count as f64),
+                        (_, pixel) if aov.is_averaged() => pixel.aovs[i] / pixel.hits.max(1) as f64,
+                        (_, pixel) => pixel.aovs[i],
+                    });
+                    (*aov, pass)
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the film to `path`, through a temporary file so that an interrupted save
+    /// doesn't lose the previous one.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(".tmp");
+
+        self.write(&mut BufWriter::new(File::create(&tmp)?))
+            .and_then(|()| Ok(fs::rename(&tmp, path)?))
+            .with_context(|| format!("failed to save film {}", path.display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("failed to open film {}", path.display()))?;
+        let len = file.metadata()?.len();
+
+        Self::read(&mut BufReader::new(file), len)
+            .with_context(|| format!("invalid film {}", path.display()))
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_all(Self::MAGIC)?;
+        w.write_all(&self.width.to_le_bytes())?;
+        w.write_all(&self.height.to_le_bytes())?;
+        w.write_all(&self.seed.to_le_bytes())?;
+        w.write_all(&(self.settings.len() as u32).to_le_bytes())?;
+        w.write_all(self.settings.as_bytes())?;
+
+        w.write_all(&(self.aovs.len() as u32).to_le_bytes())?;
+        for aov in &self.aovs {
+            let name = aov.name().as_bytes();
+            w.write_all(&[name.len() as u8])?;
+            w.write_all(name)?;
+        }
+
+        for (pixel, splat) in self.pixels.iter().zip(&self.splats) {
+            write_color(w, splat.color)?;
+            w.write_all(&splat.weight.to_le_bytes())?;
+            for &value in &pixel.aovs {
+                write_color(w, value)?;
+            }
+            w.write_all(&pixel.hits.to_le_bytes())?;
+            w.write_all(&pixel.stats.count.to_le_bytes())?;
+            w.write_all(&pixel.stats.mean.to_le_bytes())?;
+            w.write_all(&pixel.stats.m2.to_le_bytes())?;
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Reads a film written by [`Film::write`] from the `len` bytes of `r`.
+    fn read(r: &mut impl Read, len: u64) -> Result<Self> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != Self::MAGIC {
+            bail!("not a film file, or written by an incompatible version");
+        }
+
+        let width = u32::from_le_bytes(read_bytes(r)?);
+        let height = u32::from_le_bytes(read_bytes(r)?);
+        let seed = u64::from_le_bytes(read_bytes(r)?);
+
+        let settings_len = u32::from_le_bytes(read_bytes(r)?);
+        if u64::from(settings_len) > len {
+            bail!("the file is too short for its header");
+        }
+        let mut settings = vec![0; settings_len as usize];
+        r.read_exact(&mut settings)?;
+        let settings = String::from_utf8(settings)?;
+
+        let aov_count = u32::from_le_bytes(read_bytes(r)?);
+        let mut header_len = 32 + u64::from(settings_len);
+        let mut aovs = vec![];
+        for _ in 0..aov_count {
+            let [name_len] = read_bytes(r)?;
+            let mut name = vec![0; name_len as usize];
+            r.read_exact(&mut name)?;
+            aovs.push(String::from_utf8(name)?.parse()?);
+            header_len += 1 + u64::from(name_len);
+        }
+
+        // Check the size before allocating, so that a corrupt header can't ask for more
+        // memory than the file could fill
+        let pixel_len = 8 * (3 + 1 + 3 * aovs.len() as u64 + 4);
+        let pixels_len = u64::from(width)
+            .checked_mul(u64::from(height))
+            .and_then(|pixels| pixels.checked_mul(pixel_len));
+        if pixels_len != len.checked_sub(header_len) {
+            bail!("the file size doesn't match a {width}x{height} film");
+        }
+
+        let mut film = Self::new(width, height, seed, settings, aovs);
+
+        for (pixel, splat) in film.pixels.iter_mut().zip(&mut film.splats) {
+            splat.color = read_color(r)?;
+            splat.weight = f64::from_le_bytes(read_bytes(r)?);
+            for value in &mut pixel.aovs {
+                *value = read_color(r)?;
+            }
+            pixel.hits = i64::from_le_bytes(read_bytes(r)?);
+            pixel.stats = RunningStats {
+                count: i64::from_le_bytes(read_bytes(r)?),
+                mean: f64::from_le_bytes(read_bytes(r)?),
+                m2: f64::from_le_bytes(read_bytes(r)?),
+            };
+        }
+
+        Ok(film)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+}
+
+fn write_color(w: &mut impl Write, color: Color) -> Result<()> {
+    for c in [color.x(), color.y(), color.z()] {
+        w.write_all(&c.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_color(r: &mut impl Read) -> Result<Color> {
+    Ok(Color::new(
+        f64::from_le_bytes(read_bytes(r)?),
+        f64::from_le_bytes(read_bytes(r)?),
+        f64::from_le_bytes(read_bytes(r)?),
+    ))
+}
+
+fn read_bytes<const N: usize>(r: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
@@ -0,0 +1,159 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use crate::{Result, PI};
+
+/// Shape of a [`Filter`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    /// Every sample within the radius weighs the same, the plain average of the samples of
+    /// each pixel with the default radius of half a pixel.
+    #[default]
+    Box,
+    /// Weight falling off linearly to 0 at the radius.
+    Tent,
+    /// Gaussian with a standard deviation of a third of the radius, shifted down to reach
+    /// 0 at the radius.
+    Gaussian,
+    /// Mitchell–Netravali cubic with `B = C = 1/3`, sharper than the Gaussian but with
+    /// slight ringing.
+    Mitchell,
+    /// Sinc windowed by a wider sinc, the sharpest and the most ringing.
+    Lanczos,
+}
+
+impl FilterKind {
+    const NAMES: [(&'static str, Self); 5] = [
+        ("box", Self::Box),
+        ("tent", Self::Tent),
+        ("gaussian", Self::Gaussian),
+        ("mitchell", Self::Mitchell),
+        ("lanczos", Self::Lanczos),
+    ];
+
+    /// Radius in pixels the filter is usually used with.
+    pub const fn default_radius(&self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.,
+            FilterKind::Lanczos => 3.,
+        }
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::NAMES.iter().find(|(name, _)| *name == s) {
+            Some((_, kind)) => Ok(*kind),
+            None => {
+                let names: Vec<_> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "unknown filter `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Pixel reconstruction filter: every sample adds its radiance to all pixels whose centre
+/// is within `radius` pixels on both axes, weighted by the filter at its offset from the
+/// pixel centre, and each pixel is the weighted average of the samples it received.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filter {
+    kind: FilterKind,
+    radius: f64,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(FilterKind::default())
+    }
+}
+
+impl Filter {
+    /// Filter of the default radius of `kind`.
+    pub const fn new(kind: FilterKind) -> Self {
+        Self {
+            kind,
+            radius: kind.default_radius(),
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius.max(0.5);
+        self
+    }
+
+    pub const fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    pub const fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Weight of a sample `(dx, dy)` pixels away from a pixel centre, with both offsets in
+    /// `[-radius, radius)`.
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let r = self.radius;
+        let x = x.abs();
+
+        match self.kind {
+            FilterKind::Box => 1.,
+            FilterKind::Tent => (1. - x / r).max(0.),
+            FilterKind::Gaussian => {
+                let gaussian = |x: f64| {
+                    let sigma = r / 3.;
+                    (-x * x / (2. * sigma * sigma)).exp()
+                };
+                (gaussian(x) - gaussian(r)).max(0.)
+            }
+            FilterKind::Mitchell => mitchell(2. * x / r),
+            FilterKind::Lanczos => match x < r {
+                true => sinc(x) * sinc(x / r),
+                false => 0.,
+            },
+        }
+    }
+}
+
+/// Mitchell–Netravali cubic over `[0, 2)`.
+fn mitchell(x: f64) -> f64 {
+    const B: f64 = 1. / 3.;
+    const C: f64 = 1. / 3.;
+
+    let weight = if x >= 2. {
+        0.
+    } else if x > 1. {
+        (-B - 6. * C) * x.powi(3)
+            + (6. * B + 30. * C) * x * x
+            + (-12. * B - 48. * C) * x
+            + (8. * B + 24. * C)
+    } else {
+        (12. - 9. * B - 6. * C) * x.powi(3) + (-18. + 12. * B + 6. * C) * x * x + (6. - 2. * B)
+    };
+
+    weight / 6.
+}
+
+/// Normalised sinc, `sin(πx) / πx`.
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, ensure, Context};
+
+use crate::{Point3, Result};
+
+/// Dense 3D grid of values, e.g. the density or temperature of a cloud, looked up with
+/// trilinear interpolation over the unit cube. Values are stored with `x` varying fastest,
+/// then `y`, then `z`.
+#[derive(Debug, Clone)]
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    values: Vec<f32>,
+    max: f32,
+}
+
+impl VoxelGrid {
+    /// Grid of `resolution[0] * resolution[1] * resolution[2]` values.
+    pub fn new(resolution: [usize; 3], values: Vec<f32>) -> Result<Self> {
+        ensure!(
+            resolution.iter().all(|&n| n > 0),
+            "voxel grid resolution {resolution:?} is empty"
+        );
+        let count: usize = resolution.iter().product();
+        ensure!(
+            values.len() == count,
+            "voxel grid of resolution {resolution:?} needs {count} values, got {}",
+            values.len()
+        );
+        ensure!(
+            values.iter().all(|v| v.is_finite()),
+            "voxel grid has values that aren't finite"
+        );
+
+        let max = values.iter().copied().fold(0., f32::max);
+        Ok(Self {
+            resolution,
+            values,
+            max,
+        })
+    }
+
+    /// Reads a grid from `path`. Files ending in `.raw` hold bare little-endian `f32`s, and
+    /// need the `resolution`. Any other file is text, with a resolution of its own: `nx ny
+    /// nz`, then the values, separated by whitespace, with `#` starting a comment.
+    pub fn load(path: impl AsRef<Path>, resolution: Option<[usize; 3]>) -> Result<Self> {
+        let path = path.as_ref();
+        let is_raw = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("raw"));
+
+        let grid = if is_raw {
+            let Some(resolution) = resolution else {
+                bail!("raw voxel file {} needs a resolution", path.display());
+            };
+            let bytes = fs::read(path)
+                .with_context(|| format!("failed to read voxel file {}", path.display()))?;
+            ensure!(
+                bytes.len() % 4 == 0,
+                "raw voxel file {} isn't made of 32-bit floats",
+                path.display()
+            );
+            let values = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            Self::new(resolution, values)
+        } else {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read voxel file {}", path.display()))?;
+            Self::parse(&text)
+        };
+
+        grid.with_context(|| format!("invalid voxel file {}", path.display()))
+    }
+
+    /// Grid from the text format of [`VoxelGrid::load`].
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .flat_map(str::split_whitespace);
+
+        let mut resolution = [0; 3];
+        for n in &mut resolution {
+            let token = tokens.next().context("missing voxel grid resolution")?;
+            *n = token
+                .parse()
+                .with_context(|| format!("invalid voxel grid resolution {token:?}"))?;
+        }
+
+        let values = tokens
+            .map(|token| {
+                token
+                    .parse()
+                    .with_context(|| format!("invalid voxel value {token:?}"))
+            })
+            .collect::<Result<_>>()?;
+
+        Self::new(resolution, values)
+    }
+
+    pub const fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    /// Largest value in the grid, 0 if they are all negative.
+    pub fn max(&self) -> f64 {
+        f64::from(self.max)
+    }
+
+    /// Value at `p` in the unit cube, interpolated between the centres of the 8 nearest
+    /// voxels. Points outside get the value of the nearest voxel on the border.
+    pub fn lookup(&self, p: Point3) -> f64 {
+        let [nx, ny, nz] = self.resolution;
+        let split = |x: f64, n: usize| {
+            let x = (x * n as f64 - 0.5).clamp(0., (n - 1) as f64);
+            let i = (x as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as f64)
+        };
+        let (x0, x1, fx) = split(p.x(), nx);
+        let (y0, y1, fy) = split(p.y(), ny);
+        let (z0, z1, fz) = split(p.z(), nz);
+
+        let at = |x: usize, y: usize, z: usize| f64::from(self.values[(z * ny + y) * nx + x]);
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let plane = |z: usize| {
+            lerp(
+                lerp(at(x0, y0, z), at(x1, y0, z), fx),
+                lerp(at(x0, y1, z), at(x1, y1, z), fx),
+                fy,
+            )
+        };
+
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
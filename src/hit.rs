@@ -1,17 +1,38 @@
-use crate::{Interval, Material, Point3, Ray, Vec3};
+use crate::{Aabb, Color, Interval, Material, Onb, Point3, Ray, Sampler, TraversalStats, Vec3};
 
-pub type HitResult = Option<HitRecord>;
+pub type HitResult<'a> = Option<HitRecord<'a>>;
 
-pub struct HitRecord {
+/// Identifiers of the object and material that were hit, 0 when unassigned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SurfaceId {
+    pub object: u32,
+    pub material: u32,
+}
+
+pub struct HitRecord<'a> {
     pub p: Point3,
+    /// Shading normal, facing the side the ray came from.
     pub normal: Vec3,
     pub t: f64,
+    /// Surface coordinates of the hit point.
+    pub u: f64,
+    pub v: f64,
+    /// Partial derivatives of the surface position with respect to `u` and `v`.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// Shading frame with `w` along `normal` and `u` along `dpdu`.
+    pub frame: Onb,
     pub font_face: bool,
-    pub material: Material,
+    pub material: &'a Material,
+    pub id: SurfaceId,
+    /// Light given off at the point by an emissive medium, see [`crate::GridMedium`].
+    pub emission: Color,
+    /// Primitive that was hit, to find how likely light sampling was to pick the hit point.
+    pub object: Option<&'a dyn Hittable>,
 }
 
-impl HitRecord {
-    pub fn new(p: Point3, t: f64, ray: &Ray, outward_normal: Vec3, material: Material) -> Self {
+impl<'a> HitRecord<'a> {
+    pub fn new(p: Point3, t: f64, ray: &Ray, outward_normal: Vec3, material: &'a Material) -> Self {
         let font_face = ray.direction().dot(outward_normal) < 0.;
 
         let normal = if font_face {
@@ -20,18 +41,168 @@ impl HitRecord {
             -outward_normal
         };
 
+        let frame = Onb::from_w(normal);
+
         Self {
             p,
             normal,
             t,
+            u: 0.,
+            v: 0.,
+            dpdu: frame.u(),
+            dpdv: frame.v(),
+            frame,
             font_face,
             material,
+            id: SurfaceId::default(),
+            emission: Color::ZERO,
+            object: None,
         }
     }
+
+    /// Surface coordinates `(u, v)` of the hit point and the derivatives of the
+    /// position along them, used for texture lookups and to orient the shading frame.
+    pub fn with_surface_coords(mut self, u: f64, v: f64, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.u = u;
+        self.v = v;
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self.frame = Onb::from_w_tangent(self.normal, dpdu);
+        self
+    }
+
+    pub fn with_id(mut self, id: SurfaceId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    pub fn with_object(mut self, object: &'a dyn Hittable) -> Self {
+        self.object = Some(object);
+        self
+    }
+
+    /// Replaces the geometric normal used for shading, keeping it on the side the ray came from.
+    pub fn set_shading_normal(&mut self, shading_normal: Vec3) {
+        self.normal = if self.font_face {
+            shading_normal
+        } else {
+            -shading_normal
+        };
+        self.frame = Onb::from_w_tangent(self.normal, self.dpdu);
+    }
 }
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_>;
+
+    /// [`Hittable::hit`], counting the BVH nodes and objects tested into `stats`.
+    fn hit_with_stats(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        _stats: &mut TraversalStats,
+    ) -> HitResult<'_> {
+        self.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb;
+
+    /// Whether the object gives off light, and should be sampled as a light source.
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    /// Density, per unit solid angle around the origin of `ray`, with which
+    /// [`Hittable::random`] picks its direction at its time. 0 for objects that can't be
+    /// sampled.
+    fn pdf_value(&self, _ray: &Ray) -> f64 {
+        0.
+    }
+
+    /// Vector from `origin` to a point of the object visible from it at `time`, picked from
+    /// the uniform point `u` of the unit square.
+    fn random(&self, _origin: Point3, _time: f64, _u: (f64, f64)) -> Vec3 {
+        Vec3::X
+    }
+
+    /// Whether the object is a participating medium. Rays don't hit media, they are
+    /// scattered in them, see [`Hittable::sample_scatter`], and shadow rays go through them,
+    /// losing the light they absorb or scatter, see [`Hittable::transmittance`].
+    fn is_medium(&self) -> bool {
+        false
+    }
+
+    /// Point along `ray` within `ray_t` where the medium scatters it, picked with the
+    /// numbers of `sampler`, `None` when the ray gets through. Objects that aren't media
+    /// never scatter rays.
+    fn sample_scatter(
+        &self,
+        _ray: &Ray,
+        _ray_t: &Interval,
+        _sampler: &mut dyn Sampler,
+    ) -> HitResult<'_> {
+        None
+    }
+
+    /// Fraction of the light travelling along `ray` over `ray_t` that gets through the
+    /// medium, or an unbiased estimate of it drawn from `sampler`. 1 for objects that
+    /// aren't media.
+    fn transmittance(&self, _ray: &Ray, _ray_t: &Interval, _sampler: &mut dyn Sampler) -> f64 {
+        1.
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Box<H> {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        (**self).hit(ray, ray_t)
+    }
+
+    fn hit_with_stats(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        stats: &mut TraversalStats,
+    ) -> HitResult<'_> {
+        (**self).hit_with_stats(ray, ray_t, stats)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn is_light(&self) -> bool {
+        (**self).is_light()
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        (**self).pdf_value(ray)
+    }
+
+    fn random(&self, origin: Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        (**self).random(origin, time, u)
+    }
+
+    fn is_medium(&self) -> bool {
+        (**self).is_medium()
+    }
+
+    fn sample_scatter(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        sampler: &mut dyn Sampler,
+    ) -> HitResult<'_> {
+        (**self).sample_scatter(ray, ray_t, sampler)
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval, sampler: &mut dyn Sampler) -> f64 {
+        (**self).transmittance(ray, ray_t, sampler)
+    }
 }
 
 pub type HittableObj = Box<dyn Hittable>;
@@ -39,16 +210,23 @@ pub type HittableObj = Box<dyn Hittable>;
 pub type HittableList = Vec<HittableObj>;
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult {
-        let mut temp_rec: HitResult = None;
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        let mut temp_rec: HitResult<'_> = None;
+        let mut closest_so_far = ray_t.max();
 
         for hittable in self.iter() {
-            match hittable.hit(ray, ray_t) {
-                Some(rec) => temp_rec = Some(rec),
-                None => (),
+            if let Some(rec) = hittable.hit(ray, &Interval::new(ray_t.min(), closest_so_far)) {
+                closest_so_far = rec.t;
+                temp_rec = Some(rec);
             }
         }
 
         temp_rec
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.iter().fold(Aabb::EMPTY, |bbox, hittable| {
+            bbox.union(&hittable.bounding_box())
+        })
+    }
 }
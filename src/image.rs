@@ -1,4 +1,14 @@
-use image::{ImageBuffer, RgbImage};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use exr::prelude::{AnyChannel, AnyChannels, FlatSamples, SmallVec, WritableImage};
+use image::{codecs::hdr::HdrEncoder, ImageBuffer, ImageFormat, Rgb32FImage, RgbImage};
+
+use crate::{write_color, Aov, Color, OutputTransform, Passes, Result};
 
 pub struct Image {
     width: u32,
@@ -14,6 +24,14 @@ impl Image {
         Self { width, height }
     }
 
+    /// Image of exactly `width` by `height` pixels, at least 1 by 1.
+    pub fn with_height(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height: height.max(1),
+        }
+    }
+
     pub const fn width(&self) -> u32 {
         self.width
     }
@@ -28,3 +46,138 @@ impl From<Image> for RgbImage {
         ImageBuffer::new(value.width, value.height)
     }
 }
+
+impl From<Image> for Rgb32FImage {
+    fn from(value: Image) -> Self {
+        ImageBuffer::new(value.width, value.height)
+    }
+}
+
+/// Converts linear radiance to a displayable 8-bit sRGB image.
+pub fn to_rgb8(image: &Rgb32FImage, transform: &OutputTransform) -> RgbImage {
+    ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        write_color(transform.apply(Color::new(r as f64, g as f64, b as f64)))
+    })
+}
+
+/// Saves linear radiance to `path`, in `format` or the format guessed from its extension.
+///
+/// OpenEXR and Radiance HDR files keep the untouched radiance, every other format gets
+/// the 8-bit image of [`to_rgb8`] with `transform` applied.
+pub fn save_image(
+    image: &Rgb32FImage,
+    path: impl AsRef<Path>,
+    format: Option<ImageFormat>,
+    transform: &OutputTransform,
+) -> Result<()> {
+    let path = path.as_ref();
+    let format = image_format(path, format)?;
+
+    let result = match format {
+        ImageFormat::OpenExr => image.save_with_format(path, format),
+        // The generic encoders don't support Radiance HDR yet.
+        ImageFormat::Hdr => File::create(path).map_err(Into::into).and_then(|file| {
+            let pixels: Vec<_> = image.pixels().copied().collect();
+            HdrEncoder::new(BufWriter::new(file)).encode(
+                &pixels,
+                image.width() as usize,
+                image.height() as usize,
+            )
+        }),
+        _ => to_rgb8(image, transform).save_with_format(path, format),
+    };
+
+    result.with_context(|| format!("failed to save {}", path.display()))
+}
+
+/// Saves every pass of a render like [`save_image`].
+///
+/// An OpenEXR file holds all of them: the beauty image in `R`, `G` and `B` and each AOV
+/// in channels prefixed by its name, e.g. `depth.Z`. Other formats get one file per AOV
+/// next to `path`, e.g. `render.depth.png`, 8-bit ones showing [`Aov::to_rgb8`].
+pub fn save_passes(
+    passes: &Passes,
+    path: impl AsRef<Path>,
+    format: Option<ImageFormat>,
+    transform: &OutputTransform,
+) -> Result<()> {
+    let path = path.as_ref();
+    let format = image_format(path, format)?;
+
+    if format == ImageFormat::OpenExr {
+        return save_exr_layers(passes, path)
+            .with_context(|| format!("failed to save {}", path.display()));
+    }
+
+    save_image(&passes.beauty, path, Some(format), transform)?;
+
+    for (aov, image) in &passes.aovs {
+        let aov_path = aov_path(path, *aov);
+
+        match format {
+            ImageFormat::Hdr => save_image(image, &aov_path, Some(format), transform)?,
+            _ => aov
+                .to_rgb8(image)
+                .save_with_format(&aov_path, format)
+                .with_context(|| format!("failed to save {}", aov_path.display()))?,
+        }
+    }
+
+    Ok(())
+}
+
+fn image_format(path: &Path, format: Option<ImageFormat>) -> Result<ImageFormat> {
+    match format {
+        Some(format) => Ok(format),
+        None => ImageFormat::from_path(path)
+            .with_context(|| format!("unknown image format for {}", path.display())),
+    }
+}
+
+/// `path` with the name of `aov` inserted before its extension.
+fn aov_path(path: &Path, aov: Aov) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(aov.name());
+
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+
+    path.with_file_name(name)
+}
+
+fn save_exr_layers(passes: &Passes, path: &Path) -> Result<()> {
+    let channel = |name: String, image: &Rgb32FImage, component: usize| {
+        let samples = image.pixels().map(|pixel| pixel.0[component]).collect();
+        AnyChannel::new(name.as_str(), FlatSamples::F32(samples))
+    };
+
+    let mut channels = SmallVec::new();
+
+    for (component, name) in ["R", "G", "B"].into_iter().enumerate() {
+        channels.push(channel(name.to_string(), &passes.beauty, component));
+    }
+
+    for (aov, image) in &passes.aovs {
+        for (component, name) in aov.channels().iter().enumerate() {
+            channels.push(channel(
+                format!("{}.{}", aov.name(), name),
+                image,
+                component,
+            ));
+        }
+    }
+
+    let size = (
+        passes.beauty.width() as usize,
+        passes.beauty.height() as usize,
+    );
+    exr::prelude::Image::from_channels(size, AnyChannels::sort(channels))
+        .write()
+        .to_file(path)?;
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use crate::{
+    Aabb, AnimatedTransform, HitRecord, HitResult, Hittable, Interval, Onb, Point3, Ray, Sampler,
+    Transform, TraversalStats, Vec3, INFINITY,
+};
+
+/// Shared object placed in the world by an affine transform. Any number of instances can
+/// point at the same object, e.g. one [`crate::Bvh`] of a mesh, without copying it.
+///
+/// Rays are moved into the object's own space to be intersected, and the hits moved
+/// back out to world space. With an [`AnimatedTransform`] the instance moves over the
+/// shutter, every ray sees it where it is at the time of the ray.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: AnimatedTransform,
+    /// Whether `transform` is the identity at all times, so rays can be passed through as
+    /// they are.
+    identity: bool,
+    bbox: Aabb,
+    object_id: Option<u32>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, transform: impl Into<AnimatedTransform>) -> Self {
+        let transform = transform.into();
+        let mut instance = Self {
+            object,
+            transform,
+            identity: true,
+            bbox: Aabb::EMPTY,
+            object_id: None,
+        };
+        instance.set_transform(transform);
+        instance
+    }
+
+    /// Object ID reported in the object ID pass, the one of the shared object by default.
+    pub const fn with_object_id(mut self, object_id: u32) -> Self {
+        self.object_id = Some(object_id);
+        self
+    }
+
+    pub const fn transform(&self) -> &AnimatedTransform {
+        &self.transform
+    }
+
+    /// Moves the instance. The BVH it is part of must be rebuilt, see
+    /// [`crate::World::update_instances`].
+    pub fn set_transform(&mut self, transform: impl Into<AnimatedTransform>) {
+        self.transform = transform.into();
+        self.identity =
+            !self.transform.is_animated() && *self.transform.start() == Transform::IDENTITY;
+        self.bbox = self.transform.bounding_box(&self.object.bounding_box());
+    }
+
+    pub fn object(&self) -> &Arc<dyn Hittable> {
+        &self.object
+    }
+
+    /// `ray` in the object's space, for the instance placed by `transform`. The direction
+    /// isn't normalized, so that distances along the ray are the same in both spaces.
+    fn object_ray(transform: &Transform, ray: &Ray) -> Ray {
+        let to_object = transform.inverse();
+        Ray::new(
+            to_object.point(ray.origin()),
+            to_object.vector(ray.direction()),
+            ray.time(),
+        )
+    }
+
+    /// `rec` of the object, found along the ray in object space, in world space for the
+    /// instance placed by `transform`.
+    fn world_record<'a>(&'a self, transform: &Transform, mut rec: HitRecord<'a>) -> HitRecord<'a> {
+        rec.p = transform.point(rec.p);
+        rec.normal = transform.normal(rec.normal).unit();
+        rec.dpdu = transform.vector(rec.dpdu);
+        rec.dpdv = transform.vector(rec.dpdv);
+        rec.frame = Onb::from_w_tangent(rec.normal, rec.dpdu);
+        if let Some(object) = self.object_id {
+            rec.id.object = object;
+        }
+
+        rec.with_object(self)
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        self.hit_with_stats(ray, ray_t, &mut TraversalStats::default())
+    }
+
+    fn hit_with_stats(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        stats: &mut TraversalStats,
+    ) -> HitResult<'_> {
+        if self.identity {
+            let mut rec = self.object.hit_with_stats(ray, ray_t, stats)?;
+            if let Some(object) = self.object_id {
+                rec.id.object = object;
+            }
+            return Some(rec.with_object(self));
+        }
+
+        let transform = self.transform.at(ray.time());
+        let object_ray = Self::object_ray(&transform, ray);
+        let rec = self.object.hit_with_stats(&object_ray, ray_t, stats)?;
+
+        Some(self.world_record(&transform, rec))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    // The object's density is per solid angle in its own space. It is converted to a
+    // density per unit area there, to the area the transform stretches that to, and back
+    // to a density per solid angle in world space.
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        if self.identity {
+            return self.object.pdf_value(ray);
+        }
+
+        let transform = self.transform.at(ray.time());
+        let object_ray = Self::object_ray(&transform, ray);
+        let pdf = self.object.pdf_value(&object_ray);
+        if pdf <= 0. {
+            return 0.;
+        }
+
+        let Some(rec) = self
+            .object
+            .hit(&object_ray, &Interval::new(0.001, INFINITY))
+        else {
+            return 0.;
+        };
+
+        // Geometric normal, the shading one can be interpolated across a mesh
+        let mut normal = rec.dpdu.cross(rec.dpdv);
+        if normal.near_zero() {
+            normal = rec.normal;
+        }
+        let normal = normal.unit();
+
+        let object_dir = object_ray.direction();
+        let object_area_pdf = pdf * object_dir.unit().dot(normal).abs()
+            / (rec.t * rec.t * object_dir.length_squared());
+
+        let world_normal = transform.normal(normal);
+        let area_scale = transform.matrix().determinant3().abs() * world_normal.length();
+        let direction = ray.direction();
+        let cosine = direction.unit().dot(world_normal.unit()).abs();
+        if cosine * area_scale <= 0. {
+            return 0.;
+        }
+
+        object_area_pdf / area_scale * rec.t * rec.t * direction.length_squared() / cosine
+    }
+
+    fn random(&self, origin: Point3, time: f64, u: (f64, f64)) -> Vec3 {
+        if self.identity {
+            return self.object.random(origin, time, u);
+        }
+
+        let transform = self.transform.at(time);
+        let to_object = transform.inverse();
+        transform.vector(self.object.random(to_object.point(origin), time, u))
+    }
+
+    fn is_medium(&self) -> bool {
+        self.object.is_medium()
+    }
+
+    fn sample_scatter(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        sampler: &mut dyn Sampler,
+    ) -> HitResult<'_> {
+        self.bbox.hit(ray, ray_t)?;
+        if self.identity {
+            let mut rec = self.object.sample_scatter(ray, ray_t, sampler)?;
+            if let Some(object) = self.object_id {
+                rec.id.object = object;
+            }
+            return Some(rec.with_object(self));
+        }
+
+        let transform = self.transform.at(ray.time());
+        let object_ray = Self::object_ray(&transform, ray);
+        let rec = self.object.sample_scatter(&object_ray, ray_t, sampler)?;
+
+        Some(self.world_record(&transform, rec))
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval, sampler: &mut dyn Sampler) -> f64 {
+        if self.bbox.hit(ray, ray_t).is_none() {
+            return 1.;
+        }
+        if self.identity {
+            return self.object.transmittance(ray, ray_t, sampler);
+        }
+
+        let object_ray = Self::object_ray(&self.transform.at(ray.time()), ray);
+        self.object.transmittance(&object_ray, ray_t, sampler)
+    }
+}
@@ -0,0 +1,406 @@
+use std::{fmt::Debug, str::FromStr, sync::Arc};
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use crate::{
+    AovSample, Background, Color, HitRecord, HitResult, Hittable, Interval, Ray, Reflect, Result,
+    Sampler, Vec3, World, INFINITY,
+};
+
+/// Light transport algorithm, turning camera rays into radiance. [`crate::Camera`] hands
+/// every sample to one, see [`crate::Camera::with_integrator`]. The `Debug` output of an
+/// integrator is saved with its films, to resume them with the same one.
+pub trait Integrator: Debug + Send + Sync {
+    /// Radiance arriving along `ray` in `world`, with `background` seen by rays that leave
+    /// it, and what the ray interacts with first for the AOV passes. Random numbers are
+    /// drawn from `sampler`, after the ones of the camera.
+    fn radiance(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+        world: &World,
+        background: &Background,
+    ) -> (Color, Option<AovSample>);
+}
+
+/// Built-in integrators, picked by name in scene files and on the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    /// [`PathIntegrator`].
+    #[default]
+    Path,
+    /// [`WhittedIntegrator`].
+    Whitted,
+    /// [`AmbientOcclusion`].
+    AmbientOcclusion,
+    /// [`DebugIntegrator::Normal`].
+    Normal,
+    /// [`DebugIntegrator::Uv`].
+    Uv,
+    /// [`DebugIntegrator::Depth`].
+    Depth,
+    /// [`DebugIntegrator::BvhCost`].
+    BvhCost,
+}
+
+impl IntegratorKind {
+    const NAMES: [(&'static str, Self); 7] = [
+        ("path", Self::Path),
+        ("whitted", Self::Whitted),
+        ("ambient_occlusion", Self::AmbientOcclusion),
+        ("normal", Self::Normal),
+        ("uv", Self::Uv),
+        ("depth", Self::Depth),
+        ("bvh_cost", Self::BvhCost),
+    ];
+
+    /// Integrator of this kind, following paths for at most `max_depth` bounces and
+    /// looking for ambient occlusion within `ao_distance`, both unbounded when absent
+    /// (but for [`WhittedIntegrator::DEFAULT_MAX_DEPTH`]).
+    pub fn integrator(
+        &self,
+        max_depth: Option<u16>,
+        ao_distance: Option<f64>,
+    ) -> Arc<dyn Integrator> {
+        match self {
+            IntegratorKind::Path => Arc::new(PathIntegrator::new(max_depth)),
+            IntegratorKind::Whitted => Arc::new(WhittedIntegrator::new(
+                max_depth.unwrap_or(WhittedIntegrator::DEFAULT_MAX_DEPTH),
+            )),
+            IntegratorKind::AmbientOcclusion => Arc::new(AmbientOcclusion {
+                distance: ao_distance.unwrap_or(INFINITY),
+            }),
+            IntegratorKind::Normal => Arc::new(DebugIntegrator::Normal),
+            IntegratorKind::Uv => Arc::new(DebugIntegrator::Uv),
+            IntegratorKind::Depth => Arc::new(DebugIntegrator::Depth),
+            IntegratorKind::BvhCost => Arc::new(DebugIntegrator::BvhCost),
+        }
+    }
+}
+
+impl FromStr for IntegratorKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::NAMES.iter().find(|(name, _)| *name == s) {
+            Some((_, kind)) => Ok(*kind),
+            None => {
+                let names: Vec<_> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "unknown integrator `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Unidirectional path tracer.
+///
+/// Paths are traced one bounce at a time. At every diffuse bounce, or scattering in a
+/// medium, a light is sampled directly with a shadow ray, and light reached by the
+/// scattered ray is weighted against it with the power heuristic. After a few bounces
+/// paths are ended at random with Russian roulette, in proportion to how little they can
+/// still contribute, which is what keeps them finite.
+#[derive(Debug, Clone, Copy)]
+pub struct PathIntegrator {
+    /// Most bounces of a path, as a safety limit on top of Russian roulette, none when
+    /// absent.
+    pub max_depth: Option<u16>,
+}
+
+impl PathIntegrator {
+    /// Bounces before Russian roulette starts.
+    const MIN_BOUNCES: u32 = 3;
+    /// Highest probability of a path surviving Russian roulette.
+    const MAX_SURVIVAL: f64 = 0.95;
+
+    pub const fn new(max_depth: Option<u16>) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+        world: &World,
+        background: &Background,
+    ) -> (Color, Option<AovSample>) {
+        let mut ray = *ray;
+        let mut radiance = Color::ZERO;
+        let mut throughput = Color::ONE;
+        let mut first_hit = None;
+        // Density of the last diffuse bounce, `None` after a mirror-like one
+        let mut last_pdf: Option<f64> = None;
+
+        for bounce in 0u32.. {
+            if self.max_depth.is_some_and(|max| bounce >= u32::from(max)) {
+                break;
+            }
+
+            let Some(rec) = interaction(sampler, world, &ray) else {
+                radiance += throughput * background.color(&ray);
+                break;
+            };
+            if bounce == 0 {
+                first_hit = Some(AovSample::new(&rec));
+            }
+
+            let emitted = rec.material.emitted(&rec);
+            if !emitted.near_zero() {
+                let weight = match last_pdf {
+                    Some(pdf) => power_heuristic(pdf, world.light_pdf(&rec, &ray)),
+                    None => 1.,
+                };
+                radiance += weight * throughput * emitted;
+            }
+
+            let Some(scatter) = rec.material.scatter(sampler, &ray, &rec) else {
+                break;
+            };
+            if scatter.pdf.is_some() {
+                radiance += throughput * direct_light(sampler, world, &ray, &rec, true);
+            }
+
+            throughput = throughput * scatter.attenuation;
+            last_pdf = scatter.pdf;
+            ray = scatter.scatter;
+
+            if bounce + 1 >= Self::MIN_BOUNCES {
+                let survival = throughput.max_component().min(Self::MAX_SURVIVAL);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        (radiance, first_hit)
+    }
+}
+
+/// Whitted-style ray tracer: direct light only, from the lights and from the background
+/// seen by diffuse surfaces, plus perfect reflections and refractions followed up to
+/// `max_depth` times. Much faster than [`PathIntegrator`], but without indirect light.
+#[derive(Debug, Clone, Copy)]
+pub struct WhittedIntegrator {
+    pub max_depth: u16,
+}
+
+impl WhittedIntegrator {
+    /// Bounces followed when the scene doesn't give a `max_depth`, as two facing mirrors
+    /// would reflect a ray forever.
+    pub const DEFAULT_MAX_DEPTH: u16 = 50;
+
+    pub const fn new(max_depth: u16) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+        world: &World,
+        background: &Background,
+    ) -> (Color, Option<AovSample>) {
+        let mut ray = *ray;
+        let mut radiance = Color::ZERO;
+        let mut throughput = Color::ONE;
+        let mut first_hit = None;
+
+        for bounce in 0..self.max_depth {
+            let Some(rec) = interaction(sampler, world, &ray) else {
+                radiance += throughput * background.color(&ray);
+                break;
+            };
+            if bounce == 0 {
+                first_hit = Some(AovSample::new(&rec));
+            }
+
+            radiance += throughput * rec.material.emitted(&rec);
+
+            let Some(scatter) = rec.material.scatter(sampler, &ray, &rec) else {
+                break;
+            };
+            if scatter.pdf.is_none() {
+                throughput = throughput * scatter.attenuation;
+                ray = scatter.scatter;
+                continue;
+            }
+
+            // Diffuse surfaces and media end the ray, with the lights and the background they
+            // see
+            radiance += throughput * direct_light(sampler, world, &ray, &rec, false);
+            let escape = &scatter.scatter;
+            if world
+                .hit_surfaces(escape, &Interval::new(0.001, INFINITY))
+                .is_none()
+            {
+                let transmittance =
+                    world.transmittance(escape, &Interval::new(0.001, INFINITY), sampler);
+                radiance +=
+                    transmittance * throughput * scatter.attenuation * background.color(escape);
+            }
+            break;
+        }
+
+        (radiance, first_hit)
+    }
+}
+
+/// Fraction of the hemisphere around the first hit that isn't blocked within `distance`,
+/// cosine-weighted. Rays that hit nothing are black.
+#[derive(Debug, Clone, Copy)]
+pub struct AmbientOcclusion {
+    pub distance: f64,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self { distance: INFINITY }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        sampler: &mut dyn Sampler,
+        world: &World,
+        _background: &Background,
+    ) -> (Color, Option<AovSample>) {
+        let Some(rec) = world.hit(ray, &Interval::new(0.001, INFINITY)) else {
+            return (Color::ZERO, None);
+        };
+
+        let mut direction = rec.normal + Vec3::sample_unit_sphere(sampler.get_2d());
+        if direction.near_zero() {
+            direction = rec.normal;
+        }
+
+        let occlusion_ray = Ray::new(rec.p, direction.unit(), ray.time());
+        let color = match world.hit(&occlusion_ray, &Interval::new(0.001, self.distance)) {
+            Some(_) => Color::ZERO,
+            None => Color::ONE,
+        };
+
+        (color, Some(AovSample::new(&rec)))
+    }
+}
+
+/// Integrators showing a property of the first hit instead of light, to inspect scenes.
+/// Rays that hit nothing are black.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugIntegrator {
+    /// Shading normal, mapped from `[-1, 1]` to `[0, 1]`.
+    Normal,
+    /// Surface coordinates in red and green.
+    Uv,
+    /// Distance along the ray, in scene units.
+    Depth,
+    /// Number of BVH nodes and objects the camera ray was tested against, in scene units
+    /// of brightness: a negative exposure or an HDR output brings it into view.
+    BvhCost,
+}
+
+impl Integrator for DebugIntegrator {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        _sampler: &mut dyn Sampler,
+        world: &World,
+        _background: &Background,
+    ) -> (Color, Option<AovSample>) {
+        let (rec, stats) = world.hit_with_stats(ray, &Interval::new(0.001, INFINITY));
+        let first_hit = rec.as_ref().map(AovSample::new);
+
+        let color = match (self, rec) {
+            (DebugIntegrator::BvhCost, _) => Color::splat(f64::from(stats.nodes + stats.objects)),
+            (_, None) => Color::ZERO,
+            (DebugIntegrator::Normal, Some(rec)) => 0.5 * (rec.normal + Color::ONE),
+            (DebugIntegrator::Uv, Some(rec)) => Color::new(rec.u, rec.v, 0.),
+            (DebugIntegrator::Depth, Some(rec)) => Color::splat(rec.t * ray.direction().length()),
+        };
+
+        (color, first_hit)
+    }
+}
+
+/// First thing `ray` interacts with: the nearest surface it hits, or a point in front of it
+/// where a medium or the world's fog scatters it. Rays that hit nothing can still be
+/// scattered by the fog on their way out of it.
+fn interaction<'a>(sampler: &mut dyn Sampler, world: &'a World, ray: &Ray) -> HitResult<'a> {
+    let surface = world.hit_surfaces(ray, &Interval::new(0.001, INFINITY));
+    let t_max = surface.as_ref().map_or(INFINITY, |rec| rec.t);
+    let rec = world
+        .sample_media(ray, &Interval::new(0.001, t_max), sampler)
+        .or(surface);
+    let Some(fog) = world.fog() else {
+        return rec;
+    };
+
+    let u = sampler.get_1d();
+    let t_max = rec.as_ref().map_or(INFINITY, |rec| rec.t);
+    fog.sample_scatter(ray, &Interval::new(0.001, t_max), u)
+        .or(rec)
+}
+
+/// Light reaching the hit point `rec` straight from a light picked at random and scattered
+/// back along `ray`, weighted against scattering towards the light with the power
+/// heuristic when `mis` is set.
+fn direct_light(
+    sampler: &mut dyn Sampler,
+    world: &World,
+    ray: &Ray,
+    rec: &HitRecord,
+    mis: bool,
+) -> Color {
+    let u_light = sampler.get_1d();
+    let u = sampler.get_2d();
+    let Some((light, to_light, light_pdf)) = world.sample_light(rec.p, ray.time(), u_light, u)
+    else {
+        return Color::ZERO;
+    };
+
+    let f = rec.material.eval(ray, rec, to_light);
+    if f.near_zero() {
+        return Color::ZERO;
+    }
+
+    // The sampled point is at `t = 1` on the sampled light, it is visible if no surface is
+    // hit before it. Media in between only let part of its light through.
+    const EPSILON: f64 = 1e-4;
+    let shadow_ray = Ray::new(rec.p, to_light, ray.time());
+    let Some(light_rec) = light.hit(&shadow_ray, &Interval::new(1. - EPSILON, 1. + EPSILON)) else {
+        return Color::ZERO;
+    };
+    let blocker = world.hit_surfaces(&shadow_ray, &Interval::new(0.001, light_rec.t - EPSILON));
+    if blocker.is_some() {
+        return Color::ZERO;
+    }
+
+    let weight = match mis {
+        true => power_heuristic(light_pdf, rec.material.scattering_pdf(ray, rec, to_light)),
+        false => 1.,
+    };
+    let transmittance = world.transmittance(&shadow_ray, &Interval::new(0., light_rec.t), sampler);
+
+    weight * transmittance * f * light_rec.material.emitted(&light_rec) / light_pdf
+}
+
+/// Multiple importance sampling weight of a sample picked with density `pdf`, against
+/// another strategy that would have picked it with density `other_pdf`.
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0. {
+        return 0.;
+    }
+    a / (a + b)
+}
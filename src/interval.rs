@@ -1,5 +1,6 @@
 use crate::INFINITY;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     min: f64,
     max: f64,
@@ -27,6 +28,20 @@ impl Interval {
         self.max
     }
 
+    /// Smallest interval enclosing both `a` and `b`.
+    pub fn enclosing(a: &Self, b: &Self) -> Self {
+        Self::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.;
+        Self::new(self.min - padding, self.max + padding)
+    }
+
     pub fn contains(&self, x: f64) -> bool {
         self.min() <= x && x <= self.max()
     }
@@ -1,21 +1,71 @@
+mod aabb;
+mod adaptive;
+mod aov;
+mod background;
+mod blackbody;
+mod bvh;
 mod camera;
+mod denoise;
+mod film;
+mod filter;
+mod grid;
 mod hit;
 mod image;
+mod instance;
+mod integrator;
 mod interval;
 mod material;
+mod medium;
+mod obj;
+mod onb;
+mod perlin;
+mod quaternion;
 mod ray;
+mod rng;
+mod sampler;
+mod scene;
 mod sphere;
+mod texture;
+mod tile;
+mod tonemap;
+mod transform;
+mod triangle;
 mod utils;
 mod vec3;
 mod world;
 
+pub use aabb::*;
+pub use adaptive::*;
+pub use aov::*;
+pub use background::*;
+pub use blackbody::*;
+pub use bvh::*;
 pub use camera::*;
+pub use denoise::*;
+pub use film::*;
+pub use filter::*;
+pub use grid::*;
 pub use hit::*;
 pub use image::*;
+pub use instance::*;
+pub use integrator::*;
 pub use interval::*;
 pub use material::*;
+pub use medium::*;
+pub use obj::*;
+pub use onb::*;
+pub use perlin::*;
+pub use quaternion::*;
 pub use ray::*;
+pub use rng::*;
+pub use sampler::*;
+pub use scene::*;
 pub use sphere::*;
+pub use texture::*;
+pub use tile::*;
+pub use tonemap::*;
+pub use transform::*;
+pub use triangle::*;
 pub use utils::*;
 pub use vec3::*;
 pub use world::*;
@@ -1,27 +1,335 @@
-use simple_ray_tracer::{Camera, Image, Point3, Vec3, World};
+use std::{path::PathBuf, time::Duration};
 
-const OUT_FILE_NAME: &str = "output.png";
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use image::ImageFormat;
+use rand::{thread_rng, Rng, SeedableRng};
+use simple_ray_tracer::{
+    save_passes, Aov, CameraSettings, Film, FilterKind, ImageSettings, IntegratorKind, Point3,
+    RenderRng, RenderSettings, SamplerKind, Scene, ToneMap, Vec3, World,
+};
+
+/// Renders a TOML scene file, or the built-in random spheres scene, to an image.
+///
+/// Flags override the corresponding settings of the scene file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Scene file to render, the built-in random spheres scene is used when omitted
+    #[arg(short, long)]
+    scene: Option<PathBuf>,
+
+    /// Output image path
+    #[arg(short, long, default_value = "output.png")]
+    output: PathBuf,
+
+    /// Output image format (png, jpeg, exr, hdr, ...), guessed from the output extension by default.
+    /// OpenEXR and Radiance HDR keep the unclamped radiance
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<ImageFormat>,
+
+    /// Image width in pixels
+    #[arg(short = 'W', long)]
+    width: Option<u32>,
+
+    /// Image height in pixels, instead of the one given by the aspect ratio
+    #[arg(short = 'H', long, conflicts_with = "aspect_ratio")]
+    height: Option<u32>,
+
+    /// Image aspect ratio, either as a number or as `W:H`
+    #[arg(short, long, value_parser = parse_aspect_ratio)]
+    aspect_ratio: Option<f64>,
+
+    /// Samples per pixel, on average with --adaptive
+    #[arg(short = 'n', long)]
+    samples: Option<i64>,
+
+    /// Spread the samples by noise: pixels stop once below a threshold, and the rest of the
+    /// budget goes to the noisier ones
+    #[arg(long)]
+    adaptive: bool,
+
+    /// Relative noise adaptive sampling stops at, implies --adaptive
+    #[arg(long)]
+    noise_threshold: Option<f64>,
+
+    /// Minimum samples per pixel of adaptive sampling, implies --adaptive
+    #[arg(long)]
+    min_samples: Option<i64>,
+
+    /// Maximum samples per pixel of adaptive sampling, implies --adaptive
+    #[arg(long)]
+    max_samples: Option<i64>,
+
+    /// How the samples of each pixel are spread out: independent, stratified, halton or sobol
+    #[arg(long)]
+    sampler: Option<SamplerKind>,
+
+    /// Pixel reconstruction filter: box, tent, gaussian, mitchell or lanczos
+    #[arg(long)]
+    filter: Option<FilterKind>,
+
+    /// Radius of the reconstruction filter in pixels
+    #[arg(long)]
+    filter_radius: Option<f64>,
+
+    /// Light transport algorithm: path, whitted, ambient_occlusion, or the normal, uv, depth
+    /// and bvh_cost debug views
+    #[arg(long)]
+    integrator: Option<IntegratorKind>,
+
+    /// Distance within which ambient occlusion looks for occluders
+    #[arg(long)]
+    ao_distance: Option<f64>,
+
+    /// Maximum number of bounces per ray, as a safety limit: paths end with Russian roulette,
+    /// and only the whitted integrator has a cap (50) by default
+    #[arg(short = 'd', long)]
+    max_depth: Option<u16>,
+
+    /// Auxiliary passes to render along with the image, as a comma separated list of depth,
+    /// normal, albedo, position, object_id, material_id and sample_count
+    #[arg(short = 'A', long, value_delimiter = ',')]
+    aov: Vec<Aov>,
+
+    /// Denoise the rendered image
+    #[arg(long, overrides_with = "no_denoise")]
+    denoise: bool,
+
+    /// Don't denoise the rendered image, even if the scene file asks for it
+    #[arg(long, overrides_with = "denoise")]
+    no_denoise: bool,
+
+    /// Exposure adjustment in stops, for 8-bit outputs
+    #[arg(short, long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Tone mapping curve for 8-bit outputs: none, reinhard, reinhard_extended, hable or aces
+    #[arg(short, long)]
+    tone_map: Option<ToneMap>,
+
+    /// Render in passes of this many samples per pixel over the whole image
+    #[arg(long)]
+    samples_per_pass: Option<i64>,
+
+    /// Stop after the pass that ends past this many seconds, the render can be resumed from
+    /// its checkpoint
+    #[arg(long)]
+    time_limit: Option<f64>,
+
+    /// Save the accumulated samples to this file after every pass
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Carry on an interrupted render from its checkpoint, with the same scene and settings
+    #[arg(long)]
+    resume: Option<PathBuf>,
+
+    /// Save the output image after every pass, not only at the end
+    #[arg(long)]
+    save_every_pass: bool,
+
+    /// Number of worker threads, defaults to the number of available cores
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Seed for the random number generators, renders with the same seed are bit-identical.
+    /// Resumed renders use the seed of their checkpoint
+    #[arg(long, conflicts_with = "resume")]
+    seed: Option<u64>,
+
+    /// Don't print progress or status messages
+    #[arg(short, long)]
+    quiet: bool,
+}
 
 fn main() -> simple_ray_tracer::Result<()> {
-    let world = World::scene();
+    let args = Args::parse();
+
+    let film = args.resume.as_ref().map(Film::load).transpose()?;
+    let seed = match &film {
+        Some(film) => film.seed(),
+        None => args.seed.unwrap_or_else(|| thread_rng().gen()),
+    };
+
+    let (mut scene, world) = match &args.scene {
+        Some(path) => {
+            let scene = Scene::load(path)?;
+            let world = scene.world()?;
+            (scene, world)
+        }
+        None => {
+            let world = World::random_scene(&mut RenderRng::seed_from_u64(seed));
+            (builtin_scene(), world)
+        }
+    };
+
+    if let Some(width) = args.width {
+        scene.image.width = width;
+    }
+    if let Some(aspect_ratio) = args.aspect_ratio {
+        scene.image.aspect_ratio = aspect_ratio;
+        scene.image.height = None;
+    }
+    if let Some(height) = args.height {
+        scene.image.height = Some(height);
+    }
+    if let Some(samples) = args.samples {
+        scene.render.samples_per_pixel = samples;
+    }
+    if let Some(max_depth) = args.max_depth {
+        scene.render.max_depth = Some(max_depth);
+    }
+    if !args.aov.is_empty() {
+        scene.render.aovs = args.aov;
+    }
+    if args.adaptive
+        || args.noise_threshold.is_some()
+        || args.min_samples.is_some()
+        || args.max_samples.is_some()
+    {
+        let adaptive = scene.render.adaptive.get_or_insert_with(Default::default);
 
-    let img = Image::new(1200, 16. / 9.);
+        if let Some(threshold) = args.noise_threshold {
+            adaptive.threshold = threshold;
+        }
+        if let Some(min_samples) = args.min_samples {
+            adaptive.min_samples = min_samples;
+        }
+        if let Some(max_samples) = args.max_samples {
+            adaptive.max_samples = max_samples;
+        }
+    }
+    if let Some(sampler) = args.sampler {
+        scene.render.sampler = sampler;
+    }
+    if let Some(filter) = args.filter {
+        scene.render.filter = filter;
+    }
+    if let Some(radius) = args.filter_radius {
+        scene.render.filter_radius = Some(radius);
+    }
+    if let Some(integrator) = args.integrator {
+        scene.render.integrator = integrator;
+    }
+    if let Some(distance) = args.ao_distance {
+        scene.render.ao_distance = Some(distance);
+    }
+    if args.denoise || args.no_denoise {
+        scene.render.denoise = args.denoise;
+    }
+    if let Some(exposure) = args.exposure {
+        scene.output.exposure = exposure;
+    }
+    if let Some(tone_map) = args.tone_map {
+        scene.output.tone_map = tone_map;
+    }
 
-    let cam = Camera::new(
-        img,
-        10,
-        50,
-        20.,
-        Point3::new(13., 2., 3.),
-        Point3::ZERO,
-        Vec3::Y,
-        0.6,
-        10.,
-    );
+    let mut cam = scene.camera().with_progress(!args.quiet).with_seed(seed);
+    if let Some(threads) = args.threads {
+        cam = cam.with_threads(threads);
+    }
+    if let Some(samples) = args.samples_per_pass {
+        cam = cam.with_samples_per_pass(samples);
+    }
+    if let Some(seconds) = args.time_limit {
+        cam = cam.with_time_budget(Duration::try_from_secs_f64(seconds)?);
+    }
 
-    let image = cam.render(world)?;
+    let mut film = match film {
+        Some(film) => film,
+        None => cam.film()?,
+    };
+    let save = |film: &Film| {
+        save_passes(
+            &cam.develop(film)?,
+            &args.output,
+            args.format,
+            &scene.output,
+        )
+    };
 
-    image.save(OUT_FILE_NAME)?;
+    let complete = cam.render_film(&world, &mut film, |film| {
+        if let Some(path) = &args.checkpoint {
+            film.save(path)?;
+        }
+        if args.save_every_pass {
+            save(film)?;
+        }
+        Ok(())
+    })?;
+
+    save(&film)?;
+
+    if !args.quiet {
+        println!(
+            "saved {}x{} image to {}",
+            film.width(),
+            film.height(),
+            args.output.display()
+        );
+
+        if !complete {
+            match &args.checkpoint {
+                Some(path) => println!(
+                    "stopped at the time limit, carry on with --resume {}",
+                    path.display()
+                ),
+                None => println!("stopped at the time limit"),
+            }
+        }
+    }
 
     Ok(())
 }
+
+/// Settings the random spheres scene of [`World::scene`] is rendered with.
+fn builtin_scene() -> Scene {
+    Scene {
+        image: ImageSettings {
+            width: 1200,
+            aspect_ratio: 16. / 9.,
+            height: None,
+        },
+        render: RenderSettings {
+            samples_per_pixel: 10,
+            max_depth: None,
+            background: None,
+            aovs: vec![],
+            denoise: false,
+            adaptive: None,
+            sampler: SamplerKind::default(),
+            filter: FilterKind::default(),
+            filter_radius: None,
+            integrator: IntegratorKind::default(),
+            ao_distance: None,
+        },
+        camera: CameraSettings {
+            vfov: 20.,
+            lookfrom: Point3::new(13., 2., 3.),
+            lookat: Point3::ZERO,
+            vup: Vec3::Y,
+            defocus_angle: 0.6,
+            focus_dist: 10.,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn parse_format(s: &str) -> simple_ray_tracer::Result<ImageFormat> {
+    ImageFormat::from_extension(s).ok_or_else(|| anyhow!("unknown image format `{}`", s))
+}
+
+fn parse_aspect_ratio(s: &str) -> simple_ray_tracer::Result<f64> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => w.trim().parse::<f64>()? / h.trim().parse::<f64>()?,
+        None => s.parse()?,
+    };
+
+    if !(ratio.is_finite() && ratio > 0.) {
+        bail!("aspect ratio must be positive, got `{}`", s);
+    }
+
+    Ok(ratio)
+}
@@ -1,10 +1,13 @@
-use rand::{rngs::ThreadRng, Rng};
+use std::sync::Arc;
 
-use crate::{Color, HitRecord, Ray, Vec3};
+use crate::{Color, HitRecord, PhaseFunction, Ray, Sampler, SolidColor, TextureObj, Vec3, PI};
 
 pub struct Scatter {
     pub attenuation: Color,
     pub scatter: Ray,
+    /// Density with which the scattered direction was picked, `None` for mirror-like
+    /// directions that [`Reflect::eval`] can't evaluate.
+    pub pdf: Option<f64>,
 }
 
 impl Scatter {
@@ -12,41 +15,99 @@ impl Scatter {
         Self {
             attenuation,
             scatter,
+            pdf: None,
         }
     }
+
+    pub const fn with_pdf(mut self, pdf: f64) -> Self {
+        self.pdf = Some(pdf);
+        self
+    }
 }
 
 pub trait Reflect {
-    fn scatter(&self, rng: &mut ThreadRng, ray: &Ray, rec: &HitRecord) -> Option<Scatter>;
+    fn scatter<S: Sampler + ?Sized>(
+        &self,
+        sampler: &mut S,
+        ray: &Ray,
+        rec: &HitRecord,
+    ) -> Option<Scatter>;
+
+    /// Light scattered towards the ray per unit of light arriving from `direction`, the
+    /// BSDF times the cosine with the normal. Only needed where [`Scatter::pdf`] is set.
+    fn eval(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> Color {
+        Color::ZERO
+    }
+
+    /// Density with which [`Reflect::scatter`] picks `direction`.
+    fn scattering_pdf(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.
+    }
+
+    /// Light given off at the hit point, added on top of whatever is scattered.
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::ZERO
+    }
+
+    /// Base colour of the surface at the hit point, for the albedo pass.
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::ONE
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Material {
-    Lambertian(Color),
-    Metal { albedo: Color, fuzz: f64 },
+    Lambertian(TextureObj),
+    Metal {
+        albedo: TextureObj,
+        fuzz: f64,
+    },
     Dialectric(f64),
+    DiffuseLight {
+        emit: TextureObj,
+        strength: f64,
+    },
+    /// Particles of a participating medium, see [`crate::ConstantMedium`],
+    /// [`crate::GridMedium`] and [`crate::Fog`], scattering light by `phase` and tinted by
+    /// `albedo`. The rest of the light is absorbed, and given back as the emission of the
+    /// medium at the hit point.
+    Volume {
+        albedo: TextureObj,
+        phase: PhaseFunction,
+    },
 }
 
 impl Reflect for Material {
-    fn scatter(&self, rng: &mut ThreadRng, r_in: &Ray, rec: &HitRecord) -> Option<Scatter> {
+    fn scatter<S: Sampler + ?Sized>(
+        &self,
+        sampler: &mut S,
+        r_in: &Ray,
+        rec: &HitRecord,
+    ) -> Option<Scatter> {
         match self {
             Material::Lambertian(albedo) => {
-                let mut scatter_dir = rec.normal + Vec3::random_unit(rng);
+                let mut scatter_dir = rec.normal + Vec3::sample_unit_sphere(sampler.get_2d());
 
                 if scatter_dir.near_zero() {
                     scatter_dir = rec.normal
                 }
 
-                let scatter = Ray::new(rec.p, scatter_dir);
+                let scatter = Ray::new(rec.p, scatter_dir, r_in.time());
+                let attenuation = albedo.value(rec.u, rec.v, rec.p);
+                let pdf = self.scattering_pdf(r_in, rec, scatter_dir);
 
-                Some(Scatter::new(*albedo, scatter))
+                Some(Scatter::new(attenuation, scatter).with_pdf(pdf))
             }
             Material::Metal { albedo, fuzz } => {
                 let reflected = r_in.direction().unit().reflect(rec.normal);
-                let scatter = Ray::new(rec.p, reflected + *fuzz * Vec3::random_unit(rng));
+                let scatter = Ray::new(
+                    rec.p,
+                    reflected + *fuzz * Vec3::sample_unit_sphere(sampler.get_2d()),
+                    r_in.time(),
+                );
 
                 match scatter.direction().dot(rec.normal) > 0. {
-                    true => Some(Scatter::new(*albedo, scatter)),
+                    true => Some(Scatter::new(albedo.value(rec.u, rec.v, rec.p), scatter)),
                     false => None,
                 }
             }
@@ -61,21 +122,103 @@ impl Reflect for Material {
                 let cannot_refract = refraction_ratio * sin_theta > 1.;
 
                 let dir = if cannot_refract
-                    || Self::reflectance(cos_theta, refraction_ratio) > rng.gen::<f64>()
+                    || Self::reflectance(cos_theta, refraction_ratio) > sampler.get_1d()
                 {
                     unit_dir.reflect(rec.normal)
                 } else {
                     unit_dir.refract(rec.normal, refraction_ratio)
                 };
 
-                let scatter = Ray::new(rec.p, dir);
+                let scatter = Ray::new(rec.p, dir, r_in.time());
                 Some(Scatter::new(attenuation, scatter))
             }
+            Material::DiffuseLight { .. } => None,
+            Material::Volume { albedo, phase } => {
+                let direction = r_in.direction().unit();
+                let scattered = phase.sample(direction, sampler.get_2d());
+                let scatter = Ray::new(rec.p, scattered, r_in.time());
+                let pdf = phase.eval(direction, scattered);
+
+                Some(Scatter::new(albedo.value(rec.u, rec.v, rec.p), scatter).with_pdf(pdf))
+            }
+        }
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        match self {
+            Material::Lambertian(albedo) => {
+                albedo.value(rec.u, rec.v, rec.p) * self.scattering_pdf(ray, rec, direction)
+            }
+            // There is no surface to take a cosine with, the phase function is all there is
+            Material::Volume { albedo, .. } => {
+                albedo.value(rec.u, rec.v, rec.p) * self.scattering_pdf(ray, rec, direction)
+            }
+            _ => Color::ZERO,
+        }
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        match self {
+            // Cosine-weighted, `normal + unit vector` is distributed like `cos θ / π`
+            Material::Lambertian(_) => (rec.normal.dot(direction.unit()) / PI).max(0.),
+            Material::Volume { phase, .. } => phase.eval(ray.direction().unit(), direction.unit()),
+            _ => 0.,
+        }
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        match self {
+            Material::DiffuseLight { emit, strength } => {
+                *strength * emit.value(rec.u, rec.v, rec.p)
+            }
+            Material::Volume { albedo, .. } => {
+                (Color::ONE - albedo.value(rec.u, rec.v, rec.p)) * rec.emission
+            }
+            _ => Color::ZERO,
+        }
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        match self {
+            Material::Lambertian(albedo)
+            | Material::Metal { albedo, .. }
+            | Material::Volume { albedo, .. } => albedo.value(rec.u, rec.v, rec.p),
+            Material::Dialectric(_) => Color::ONE,
+            Material::DiffuseLight { emit, .. } => emit.value(rec.u, rec.v, rec.p),
         }
     }
 }
 
 impl Material {
+    pub fn lambertian(albedo: Color) -> Self {
+        Self::Lambertian(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn metal(albedo: Color, fuzz: f64) -> Self {
+        Self::Metal {
+            albedo: Arc::new(SolidColor::new(albedo)),
+            fuzz,
+        }
+    }
+
+    pub fn diffuse_light(emit: Color, strength: f64) -> Self {
+        Self::DiffuseLight {
+            emit: Arc::new(SolidColor::new(emit)),
+            strength,
+        }
+    }
+
+    pub fn volume(albedo: Color, phase: PhaseFunction) -> Self {
+        Self::Volume {
+            albedo: Arc::new(SolidColor::new(albedo)),
+            phase,
+        }
+    }
+
+    pub const fn is_emissive(&self) -> bool {
+        matches!(self, Material::DiffuseLight { .. })
+    }
+
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         let mut r0 = (1. - ref_idx) / (1. + ref_idx);
         r0 = r0 * r0;
@@ -0,0 +1,379 @@
+use std::sync::Arc;
+
+use crate::{
+    blackbody, Aabb, Color, HitRecord, HitResult, Hittable, Interval, Material, Onb, Point3, Ray,
+    Sampler, SurfaceId, Vec3, VoxelGrid, INFINITY, PI,
+};
+
+/// Distribution of the directions light is scattered to inside a medium.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PhaseFunction {
+    /// Every direction alike.
+    #[default]
+    Isotropic,
+    /// Henyey–Greenstein lobe of asymmetry `g` in `(-1, 1)`: the mean cosine between the
+    /// incoming and scattered directions, positive for forward scattering. Build it with
+    /// [`PhaseFunction::new`], which keeps `g` away from 0.
+    HenyeyGreenstein(f64),
+}
+
+impl PhaseFunction {
+    /// Henyey–Greenstein phase function of asymmetry `g`, isotropic for `g` so close to 0
+    /// that sampling the lobe would lose precision.
+    pub fn new(g: f64) -> Self {
+        if g.abs() < 1e-3 {
+            PhaseFunction::Isotropic
+        } else {
+            PhaseFunction::HenyeyGreenstein(g.clamp(-0.999, 0.999))
+        }
+    }
+
+    /// Density of scattering light travelling along `direction` to `scattered`, per unit
+    /// solid angle. Both need to be unit vectors.
+    pub fn eval(&self, direction: Vec3, scattered: Vec3) -> f64 {
+        match *self {
+            PhaseFunction::Isotropic => 1. / (4. * PI),
+            PhaseFunction::HenyeyGreenstein(g) => {
+                let denom = 1. + g * g - 2. * g * direction.dot(scattered);
+                (1. - g * g) / (4. * PI * denom * denom.max(0.).sqrt())
+            }
+        }
+    }
+
+    /// Direction light travelling along the unit vector `direction` is scattered to, picked
+    /// from the uniform point `(u, v)` with density [`PhaseFunction::eval`].
+    pub fn sample(&self, direction: Vec3, (u, v): (f64, f64)) -> Vec3 {
+        let cos_theta = match *self {
+            PhaseFunction::Isotropic => 1. - 2. * u,
+            PhaseFunction::HenyeyGreenstein(g) => {
+                let s = (1. - g * g) / (1. - g + 2. * g * u);
+                ((1. + g * g - s * s) / (2. * g)).clamp(-1., 1.)
+            }
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * v;
+
+        Onb::from_w(direction).local(Vec3::new(
+            phi.cos() * sin_theta,
+            phi.sin() * sin_theta,
+            cos_theta,
+        ))
+    }
+}
+
+/// Volume of constant density filling the inside of `boundary`, which must be closed, like
+/// fog or smoke. Rays are scattered at a random distance into it, or pass through it.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    density: f64,
+    material: Material,
+}
+
+impl ConstantMedium {
+    /// Medium in `boundary` scattering light as `material` does, normally a
+    /// [`Material::Volume`]. The density is the chance per unit of distance of a ray being
+    /// scattered.
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, material: Material) -> Self {
+        Self {
+            boundary,
+            density,
+            material,
+        }
+    }
+
+    /// Part of `ray_t` over which `ray` is inside the boundary, and the IDs of the
+    /// boundary.
+    fn segment(&self, ray: &Ray, ray_t: &Interval) -> Option<(Interval, SurfaceId)> {
+        let enter = self.boundary.hit(ray, &Interval::UNIVERSE)?;
+        let exit = self
+            .boundary
+            .hit(ray, &Interval::new(enter.t + 0.0001, INFINITY))?;
+
+        let t_enter = enter.t.max(ray_t.min());
+        let t_exit = exit.t.min(ray_t.max());
+        (t_enter < t_exit).then(|| (Interval::new(t_enter, t_exit), enter.id))
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, _ray: &Ray, _ray_t: &Interval) -> HitResult<'_> {
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn is_medium(&self) -> bool {
+        true
+    }
+
+    fn sample_scatter(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        sampler: &mut dyn Sampler,
+    ) -> HitResult<'_> {
+        let (segment, id) = self.segment(ray, ray_t)?;
+
+        let speed = ray.direction().length();
+        let distance = -(1. - sampler.get_1d()).ln() / self.density;
+        let t = segment.min() + distance / speed;
+        if t >= segment.max() {
+            return None;
+        }
+
+        Some(
+            HitRecord::new(ray.at(t), t, ray, -ray.direction() / speed, &self.material)
+                .with_id(id)
+                .with_object(self),
+        )
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval, _sampler: &mut dyn Sampler) -> f64 {
+        match self.segment(ray, ray_t) {
+            Some((segment, _)) => (-self.density * segment.size() * ray.direction().length()).exp(),
+            None => 1.,
+        }
+    }
+}
+
+/// Medium of varying density filling a box, like a cloud or the smoke of an explosion.
+/// The density is looked up in a [`VoxelGrid`] stretched over the box, and the medium can
+/// glow like a black body at the temperatures of another grid.
+///
+/// Rays are scattered by delta tracking: distances are picked as if the whole box were as
+/// dense as its densest voxel, and each point reached scatters the ray in proportion to
+/// its actual density, or lets it go on. Shadow rays are attenuated by ratio tracking,
+/// multiplying the fractions of light let through at the same points.
+pub struct GridMedium {
+    bounds: Aabb,
+    density: VoxelGrid,
+    density_scale: f64,
+    temperature: Option<VoxelGrid>,
+    /// Light given off at evenly spaced temperatures, from 0 to the hottest voxel.
+    emission: Vec<Color>,
+    material: Material,
+    id: SurfaceId,
+}
+
+impl GridMedium {
+    const EMISSION_STEPS: usize = 256;
+
+    /// Medium over `bounds` scattering light as `material` does, normally a
+    /// [`Material::Volume`]. The values of `density` times `density_scale` are the chance
+    /// per unit of distance of a ray being scattered.
+    pub fn new(bounds: Aabb, density: VoxelGrid, density_scale: f64, material: Material) -> Self {
+        Self {
+            bounds,
+            density,
+            density_scale,
+            temperature: None,
+            emission: vec![],
+            material,
+            id: SurfaceId::default(),
+        }
+    }
+
+    /// Makes the medium give off the light of a black body, see [`blackbody`], times
+    /// `strength`, at the temperatures of `temperature` in kelvins. Like any medium, it
+    /// only gives off as much light as it absorbs, so it needs an albedo below 1.
+    pub fn with_temperature(mut self, temperature: VoxelGrid, strength: f64) -> Self {
+        let max = temperature.max();
+        self.emission = (0..=Self::EMISSION_STEPS)
+            .map(|i| strength * blackbody(max * i as f64 / Self::EMISSION_STEPS as f64))
+            .collect();
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Identifiers reported in the object and material ID passes.
+    pub const fn with_id(mut self, id: SurfaceId) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Highest density of the medium, which bounds the density at every point.
+    fn majorant(&self) -> f64 {
+        self.density.max() * self.density_scale
+    }
+
+    /// Point `p` of the box in the unit cube the grids are stretched over.
+    fn grid_point(&self, p: Point3) -> Point3 {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        Point3::new(
+            (p.x() - min.x()) / (max.x() - min.x()),
+            (p.y() - min.y()) / (max.y() - min.y()),
+            (p.z() - min.z()) / (max.z() - min.z()),
+        )
+    }
+
+    fn density_at(&self, p: Point3) -> f64 {
+        self.density.lookup(p).max(0.) * self.density_scale
+    }
+
+    fn emission_at(&self, p: Point3) -> Color {
+        let Some(temperature) = &self.temperature else {
+            return Color::ZERO;
+        };
+        let max = temperature.max();
+        if max <= 0. {
+            return Color::ZERO;
+        }
+
+        let x = (temperature.lookup(p) / max).clamp(0., 1.) * Self::EMISSION_STEPS as f64;
+        let i = (x as usize).min(Self::EMISSION_STEPS - 1);
+        let f = x - i as f64;
+        (1. - f) * self.emission[i] + f * self.emission[i + 1]
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, _ray: &Ray, _ray_t: &Interval) -> HitResult<'_> {
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn is_medium(&self) -> bool {
+        true
+    }
+
+    fn sample_scatter(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        sampler: &mut dyn Sampler,
+    ) -> HitResult<'_> {
+        let majorant = self.majorant();
+        if majorant <= 0. {
+            return None;
+        }
+        let segment = self.bounds.clip(ray, ray_t)?;
+
+        let speed = ray.direction().length();
+        let mut t = segment.min();
+        loop {
+            t -= (1. - sampler.get_1d()).ln() / (majorant * speed);
+            if t >= segment.max() {
+                return None;
+            }
+
+            let p = self.grid_point(ray.at(t));
+            if sampler.get_1d() * majorant < self.density_at(p) {
+                return Some(
+                    HitRecord::new(ray.at(t), t, ray, -ray.direction() / speed, &self.material)
+                        .with_emission(self.emission_at(p))
+                        .with_id(self.id)
+                        .with_object(self),
+                );
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval, sampler: &mut dyn Sampler) -> f64 {
+        let majorant = self.majorant();
+        let Some(segment) = self.bounds.clip(ray, ray_t) else {
+            return 1.;
+        };
+        if majorant <= 0. {
+            return 1.;
+        }
+
+        let speed = ray.direction().length();
+        let mut transmittance = 1.;
+        let mut t = segment.min();
+        loop {
+            t -= (1. - sampler.get_1d()).ln() / (majorant * speed);
+            if t >= segment.max() {
+                return transmittance;
+            }
+
+            transmittance *= 1. - self.density_at(self.grid_point(ray.at(t))) / majorant;
+
+            // Russian roulette, so rays deep into dense media stop early
+            if transmittance < 0.1 {
+                if sampler.get_1d() < 0.5 {
+                    return 0.;
+                }
+                transmittance *= 2.;
+            }
+        }
+    }
+}
+
+/// Medium of constant density filling a ball around the scene, like haze or atmospheric
+/// fog, see [`crate::World::with_fog`]. It fills the space between surfaces inside the
+/// ball, the same for every ray wherever its path went, and rays that leave the ball see
+/// the background through what they crossed of it.
+#[derive(Debug, Clone)]
+pub struct Fog {
+    density: f64,
+    center: Point3,
+    radius: f64,
+    material: Material,
+}
+
+impl Fog {
+    /// Fog filling the ball of `radius` around `center`, usually the camera, scattering
+    /// light with `phase` and tinted by `albedo`. The density is the chance per unit of
+    /// distance of a ray being scattered.
+    pub fn new(
+        density: f64,
+        albedo: Color,
+        phase: PhaseFunction,
+        center: Point3,
+        radius: f64,
+    ) -> Self {
+        Self {
+            density,
+            center,
+            radius,
+            material: Material::volume(albedo, phase),
+        }
+    }
+
+    pub const fn density(&self) -> f64 {
+        self.density
+    }
+
+    /// Fraction of the light travelling along `ray` over `ray_t` that gets through the fog
+    /// without being scattered.
+    pub fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> f64 {
+        match self.segment(ray, ray_t) {
+            Some(segment) => (-self.density * segment.size() * ray.direction().length()).exp(),
+            None => 1.,
+        }
+    }
+
+    /// Point along `ray` within `ray_t` where the fog scatters it, picked from the uniform
+    /// `u` in proportion to [`Fog::transmittance`], `None` when the ray gets through.
+    pub fn sample_scatter(&self, ray: &Ray, ray_t: &Interval, u: f64) -> HitResult<'_> {
+        let segment = self.segment(ray, ray_t)?;
+
+        let distance = -(1. - u).ln() / self.density;
+        let t = segment.min() + distance / ray.direction().length();
+        (t < segment.max())
+            .then(|| HitRecord::new(ray.at(t), t, ray, -ray.direction().unit(), &self.material))
+    }
+
+    /// Part of `ray_t` over which `ray` is inside the ball.
+    fn segment(&self, ray: &Ray, ray_t: &Interval) -> Option<Interval> {
+        let oc = self.center - ray.origin();
+        let a = ray.direction().length_squared();
+        let h = ray.direction().dot(oc);
+        let c = oc.length_squared() - self.radius * self.radius;
+
+        let discriminant = h * h - a * c;
+        if discriminant < 0. {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+
+        let t_enter = ((h - sqrtd) / a).max(ray_t.min());
+        let t_exit = ((h + sqrtd) / a).min(ray_t.max());
+        (t_enter < t_exit).then(|| Interval::new(t_enter, t_exit))
+    }
+}
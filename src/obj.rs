@@ -0,0 +1,364 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::{
+    Color, HittableList, ImageTexture, Material, Point3, Result, TextureObj, TriangleMesh, Vec3,
+};
+
+/// Loads a Wavefront `.obj` file, along with any `.mtl` libraries it references,
+/// into one triangle mesh per material.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<HittableList> {
+    Ok(load_obj_meshes(path)?
+        .into_iter()
+        .flat_map(|(_, mesh)| mesh.into_hittables())
+        .collect())
+}
+
+/// Meshes of [`load_obj`], one for each run of faces sharing a material, in file order,
+/// with the name of that material. Faces before any `usemtl` get an empty name.
+pub fn load_obj_meshes(path: impl AsRef<Path>) -> Result<Vec<(String, TriangleMesh)>> {
+    let path = path.as_ref();
+    let source =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    let mut positions: Vec<Point3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut texcoords: Vec<[f64; 2]> = vec![];
+    let mut materials: HashMap<String, Material> = HashMap::new();
+    let mut groups: Vec<(String, MeshBuilder)> = vec![(
+        String::new(),
+        MeshBuilder::new(Material::lambertian(Color::splat(0.8))),
+    )];
+
+    for (n, line) in source.lines().enumerate() {
+        let mut parse_line = || -> Result<()> {
+            let mut tokens = statement(line);
+            let Some(keyword) = tokens.next() else {
+                return Ok(());
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            match keyword {
+                "v" => positions.push(parse_vec3(&args)?),
+                "vn" => normals.push(parse_vec3(&args)?),
+                "vt" => {
+                    let u = parse_f64(&args)?;
+                    let v = if args.len() > 1 {
+                        parse_f64(&args[1..])?
+                    } else {
+                        0.
+                    };
+                    texcoords.push([u, v]);
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        bail!("a face needs at least 3 vertices, got {}", args.len());
+                    }
+
+                    let corners = args
+                        .iter()
+                        .map(|arg| {
+                            parse_corner(arg, positions.len(), texcoords.len(), normals.len())
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+
+                    let (_, group) = groups.last_mut().expect("there is always a current group");
+                    // Polygons are triangulated as a fan around their first corner
+                    for i in 1..corners.len() - 1 {
+                        group.push_face([corners[0], corners[i], corners[i + 1]]);
+                    }
+                }
+                "mtllib" => {
+                    let dir = path.parent().unwrap_or(Path::new(""));
+
+                    for lib in args {
+                        materials.extend(load_mtl(dir.join(lib))?);
+                    }
+                }
+                "usemtl" => {
+                    let name = args.join(" ");
+                    let material = materials
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| anyhow!("unknown material `{}`", name))?;
+
+                    groups.push((name, MeshBuilder::new(material)));
+                }
+                // Grouping and free-form geometry don't affect the meshes
+                _ => (),
+            }
+
+            Ok(())
+        };
+
+        parse_line().with_context(|| format!("{}:{}", path.display(), n + 1))?;
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, g)| !g.faces.is_empty())
+        .map(|(name, group)| {
+            let mesh = group
+                .build(&positions, &texcoords, &normals)
+                .with_context(|| format!("invalid mesh in {}", path.display()))?;
+            Ok((name, mesh))
+        })
+        .collect()
+}
+
+/// Parses a `.mtl` library into named materials.
+///
+/// Wavefront materials are approximated as follows: transparent ones (`d` < 1 or a
+/// refraction `illum` model) become [`Material::Dialectric`] with index `Ni`, mirror-like
+/// ones (`illum` 3, 5 or 8) become [`Material::Metal`] tinted by `Ks` with a fuzz derived
+/// from `Ns`, and everything else is [`Material::Lambertian`] with colour `Kd`, or the
+/// image `map_Kd` when present.
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, Material>> {
+    let path = path.as_ref();
+    let source =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlParams)> = None;
+
+    for (n, line) in source.lines().enumerate() {
+        let mut parse_line = || -> Result<()> {
+            let mut tokens = statement(line);
+            let Some(keyword) = tokens.next() else {
+                return Ok(());
+            };
+            let args: Vec<&str> = tokens.collect();
+
+            if keyword == "newmtl" {
+                if let Some((name, params)) = current.take() {
+                    materials.insert(name, params.material());
+                }
+
+                current = Some((args.join(" "), MtlParams::default()));
+                return Ok(());
+            }
+
+            let Some((_, params)) = current.as_mut() else {
+                bail!("`{}` appears before any `newmtl`", keyword);
+            };
+
+            match keyword {
+                "Kd" => params.kd = parse_vec3(&args)?,
+                "Ks" => params.ks = parse_vec3(&args)?,
+                "Ns" => params.ns = parse_f64(&args)?,
+                "Ni" => params.ni = parse_f64(&args)?,
+                "d" => params.d = parse_f64(&args)?,
+                "Tr" => params.d = 1. - parse_f64(&args)?,
+                "illum" => params.illum = parse_f64(&args)? as u8,
+                // Options such as `-s` or `-o` precede the file name, which comes last
+                "map_Kd" => {
+                    let file = args.last().ok_or_else(|| anyhow!("expected a file name"))?;
+                    let dir = path.parent().unwrap_or(Path::new(""));
+                    params.map_kd = Some(Arc::new(ImageTexture::load(dir.join(file))?));
+                }
+                // Ambient/emissive terms and other texture maps are not supported yet
+                _ => (),
+            }
+
+            Ok(())
+        };
+
+        parse_line().with_context(|| format!("{}:{}", path.display(), n + 1))?;
+    }
+
+    if let Some((name, params)) = current {
+        materials.insert(name, params.material());
+    }
+
+    Ok(materials)
+}
+
+struct MtlParams {
+    kd: Color,
+    ks: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u8,
+    map_kd: Option<TextureObj>,
+}
+
+impl Default for MtlParams {
+    fn default() -> Self {
+        Self {
+            kd: Color::splat(0.8),
+            ks: Color::ZERO,
+            ns: 0.,
+            ni: 1.5,
+            d: 1.,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+impl MtlParams {
+    fn material(&self) -> Material {
+        match self.illum {
+            4 | 6 | 7 | 9 => Material::Dialectric(self.ni),
+            _ if self.d < 1. => Material::Dialectric(self.ni),
+            3 | 5 | 8 => {
+                let albedo = if self.ks.near_zero() {
+                    self.kd
+                } else {
+                    self.ks
+                };
+                // Usual Phong exponent to roughness conversion
+                let fuzz = (2. / (self.ns.max(0.) + 2.)).sqrt();
+
+                Material::metal(albedo, fuzz)
+            }
+            _ => match &self.map_kd {
+                Some(texture) => Material::Lambertian(texture.clone()),
+                None => Material::lambertian(self.kd),
+            },
+        }
+    }
+}
+
+/// Faces sharing one material, with vertices deduplicated per position/texcoord/normal triple.
+struct MeshBuilder {
+    material: Material,
+    vertices: HashMap<Corner, usize>,
+    corners: Vec<Corner>,
+    faces: Vec<[usize; 3]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Corner {
+    position: usize,
+    texcoord: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl MeshBuilder {
+    fn new(material: Material) -> Self {
+        Self {
+            material,
+            vertices: HashMap::new(),
+            corners: vec![],
+            faces: vec![],
+        }
+    }
+
+    fn push_face(&mut self, face: [Corner; 3]) {
+        let face = face.map(|corner| {
+            *self.vertices.entry(corner).or_insert_with(|| {
+                self.corners.push(corner);
+                self.corners.len() - 1
+            })
+        });
+
+        self.faces.push(face);
+    }
+
+    fn build(
+        self,
+        positions: &[Point3],
+        texcoords: &[[f64; 2]],
+        normals: &[Vec3],
+    ) -> Result<TriangleMesh> {
+        let mesh_positions = self.corners.iter().map(|c| positions[c.position]).collect();
+        let mut mesh = TriangleMesh::new(mesh_positions, self.faces, self.material)?;
+
+        if let Some(mesh_uvs) = self
+            .corners
+            .iter()
+            .map(|c| c.texcoord.map(|i| texcoords[i]))
+            .collect::<Option<Vec<_>>>()
+        {
+            mesh = mesh.with_uvs(mesh_uvs)?;
+        }
+
+        // Smooth shading only when every vertex of the group carries a normal
+        match self
+            .corners
+            .iter()
+            .map(|c| c.normal.map(|i| normals[i]))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(mesh_normals) => mesh.with_normals(mesh_normals),
+            None => Ok(mesh),
+        }
+    }
+}
+
+/// Tokens of a line, with comments stripped.
+fn statement(line: &str) -> std::str::SplitWhitespace<'_> {
+    line.split('#')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+}
+
+fn parse_f64(args: &[&str]) -> Result<f64> {
+    let arg = args.first().ok_or_else(|| anyhow!("expected a number"))?;
+    arg.parse()
+        .with_context(|| format!("invalid number `{}`", arg))
+}
+
+fn parse_vec3(args: &[&str]) -> Result<Vec3> {
+    if args.len() < 3 {
+        bail!("expected 3 numbers, got {}", args.len());
+    }
+
+    Ok(Vec3::new(
+        parse_f64(&args[0..])?,
+        parse_f64(&args[1..])?,
+        parse_f64(&args[2..])?,
+    ))
+}
+
+/// Parses a face corner `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero-based indices.
+fn parse_corner(
+    arg: &str,
+    position_count: usize,
+    texcoord_count: usize,
+    normal_count: usize,
+) -> Result<Corner> {
+    let mut indices = arg.split('/');
+
+    let position = resolve_index(indices.next(), position_count)?
+        .ok_or_else(|| anyhow!("face corner `{}` has no vertex index", arg))?;
+    let texcoord = resolve_index(indices.next(), texcoord_count)?;
+    let normal = resolve_index(indices.next(), normal_count)?;
+
+    Ok(Corner {
+        position,
+        texcoord,
+        normal,
+    })
+}
+
+/// OBJ indices are one-based, negative ones count back from the last element read.
+fn resolve_index(index: Option<&str>, count: usize) -> Result<Option<usize>> {
+    let Some(index) = index.filter(|i| !i.is_empty()) else {
+        return Ok(None);
+    };
+
+    let value: i64 = index
+        .parse()
+        .with_context(|| format!("invalid index `{}`", index))?;
+
+    let resolved = match value {
+        v if v > 0 => v - 1,
+        v if v < 0 => count as i64 + v,
+        _ => bail!("index 0 is not valid, OBJ indices start at 1"),
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        bail!(
+            "index {} is out of range, {} elements defined so far",
+            value,
+            count
+        );
+    }
+
+    Ok(Some(resolved as usize))
+}
@@ -0,0 +1,64 @@
+use crate::Vec3;
+
+/// Orthonormal basis, with `w` usually along a surface normal.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// Basis around `w` with an arbitrary but continuous choice of tangent
+    /// (Duff et al., "Building an Orthonormal Basis, Revisited").
+    pub fn from_w(w: Vec3) -> Self {
+        let w = w.unit();
+        let sign = 1_f64.copysign(w.z());
+        let a = -1. / (sign + w.z());
+        let b = w.x() * w.y() * a;
+
+        let u = Vec3::new(1. + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Vec3::new(b, sign + w.y() * w.y() * a, -w.y());
+
+        Self { u, v, w }
+    }
+
+    /// Basis around `w` whose `u` axis follows `tangent` as closely as possible.
+    pub fn from_w_tangent(w: Vec3, tangent: Vec3) -> Self {
+        let w = w.unit();
+        let u = tangent - tangent.dot(w) * w;
+
+        if u.near_zero() {
+            return Self::from_w(w);
+        }
+
+        let u = u.unit();
+        Self {
+            u,
+            v: w.cross(u),
+            w,
+        }
+    }
+
+    pub const fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub const fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub const fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Converts coordinates expressed in this basis to world space.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Converts a world space vector to coordinates in this basis.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
@@ -0,0 +1,106 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{Point3, Vec3};
+
+/// Gradient noise on a lattice of random unit vectors, picked through three random
+/// permutations, with Hermite smoothing of the trilinear interpolation. This is the noise
+/// of "Ray Tracing: The Next Week", not the fixed gradient set and quintic fade of Ken
+/// Perlin's "Improving Noise".
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    const POINT_COUNT: usize = 256;
+
+    pub fn new<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let gradients = (0..Self::POINT_COUNT)
+            .map(|_| Vec3::gen_range(rng, -1., 1.).unit())
+            .collect();
+
+        Self {
+            gradients,
+            perm_x: Self::permutation(rng),
+            perm_y: Self::permutation(rng),
+            perm_z: Self::permutation(rng),
+        }
+    }
+
+    /// Smooth noise in [-1, 1].
+    pub fn noise(&self, p: Point3) -> f64 {
+        let (u, v, w) = (
+            p.x() - p.x().floor(),
+            p.y() - p.y().floor(),
+            p.z() - p.z().floor(),
+        );
+        let (i, j, k) = (
+            p.x().floor() as i64,
+            p.y().floor() as i64,
+            p.z().floor() as i64,
+        );
+
+        let mask = Self::POINT_COUNT as i64 - 1;
+        let mut c = [[[Vec3::ZERO; 2]; 2]; 2];
+
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, gradient) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[((i + di as i64) & mask) as usize]
+                        ^ self.perm_y[((j + dj as i64) & mask) as usize]
+                        ^ self.perm_z[((k + dk as i64) & mask) as usize];
+                    *gradient = self.gradients[index];
+                }
+            }
+        }
+
+        Self::trilinear_interp(&c, u, v, w)
+    }
+
+    /// Sum of `depth` octaves of noise with halving amplitude, always positive.
+    pub fn turbulence(&self, p: Point3, depth: u32) -> f64 {
+        let mut accum = 0.;
+        let mut temp_p = p;
+        let mut weight = 1.;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(temp_p);
+            weight *= 0.5;
+            temp_p *= 2.;
+        }
+
+        accum.abs()
+    }
+
+    fn permutation<R: Rng + ?Sized>(rng: &mut R) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..Self::POINT_COUNT).collect();
+        p.shuffle(rng);
+        p
+    }
+
+    fn trilinear_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let uu = u * u * (3. - 2. * u);
+        let vv = v * v * (3. - 2. * v);
+        let ww = w * w * (3. - 2. * w);
+        let mut accum = 0.;
+
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, gradient) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+
+                    accum += (fi * uu + (1. - fi) * (1. - uu))
+                        * (fj * vv + (1. - fj) * (1. - vv))
+                        * (fk * ww + (1. - fk) * (1. - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+
+        accum
+    }
+}
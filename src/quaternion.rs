@@ -0,0 +1,178 @@
+use crate::{Mat4, Vec3};
+
+/// Unit quaternion `w + v`, a rotation that can be interpolated smoothly with
+/// [`Quaternion::slerp`].
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    pub w: f64,
+    pub v: Vec3,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Quaternion {
+    pub const IDENTITY: Self = Self::new(1., Vec3::ZERO);
+
+    pub const fn new(w: f64, v: Vec3) -> Self {
+        Self { w, v }
+    }
+
+    /// Rotation of the upper left 3x3 block of `m`, which must be a rotation matrix.
+    pub fn from_matrix(m: &Mat4) -> Self {
+        let m = m.rows();
+        let trace = m[0][0] + m[1][1] + m[2][2];
+
+        // Computed from the largest of `w`, `x`, `y` and `z` to stay accurate
+        let q = if trace > 0. {
+            let s = (trace + 1.).sqrt();
+            let f = 0.5 / s;
+            Self::new(
+                0.5 * s,
+                Vec3::new(
+                    (m[2][1] - m[1][2]) * f,
+                    (m[0][2] - m[2][0]) * f,
+                    (m[1][0] - m[0][1]) * f,
+                ),
+            )
+        } else if m[0][0] >= m[1][1] && m[0][0] >= m[2][2] {
+            let s = (m[0][0] - m[1][1] - m[2][2] + 1.).sqrt();
+            let f = 0.5 / s;
+            Self::new(
+                (m[2][1] - m[1][2]) * f,
+                Vec3::new(0.5 * s, (m[0][1] + m[1][0]) * f, (m[0][2] + m[2][0]) * f),
+            )
+        } else if m[1][1] >= m[2][2] {
+            let s = (m[1][1] - m[0][0] - m[2][2] + 1.).sqrt();
+            let f = 0.5 / s;
+            Self::new(
+                (m[0][2] - m[2][0]) * f,
+                Vec3::new((m[0][1] + m[1][0]) * f, 0.5 * s, (m[1][2] + m[2][1]) * f),
+            )
+        } else {
+            let s = (m[2][2] - m[0][0] - m[1][1] + 1.).sqrt();
+            let f = 0.5 / s;
+            Self::new(
+                (m[1][0] - m[0][1]) * f,
+                Vec3::new((m[0][2] + m[2][0]) * f, (m[1][2] + m[2][1]) * f, 0.5 * s),
+            )
+        };
+
+        q.unit()
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        let (w, x, y, z) = (self.w, self.v.x(), self.v.y(), self.v.z());
+
+        Mat4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn dot(&self, other: &Self) -> f64 {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    pub fn unit(&self) -> Self {
+        let length = self.dot(self).sqrt();
+        Self::new(self.w / length, self.v / length)
+    }
+
+    /// Angle in radians of the rotation from `self` to `other`.
+    pub fn angle_to(&self, other: &Self) -> f64 {
+        2. * self.dot(other).abs().min(1.).acos()
+    }
+
+    /// Spherical linear interpolation from `self` at `t = 0` to `other` at `t = 1`, at a
+    /// constant angular speed and along the shortest way around.
+    pub fn slerp(&self, other: &Self, t: f64) -> Self {
+        // `q` and `-q` are the same rotation, the one closer to `self` is the short way
+        let (other, cos) = match self.dot(other) {
+            cos if cos < 0. => (Self::new(-other.w, -other.v), -cos),
+            cos => (*other, cos),
+        };
+
+        // Nearly parallel, where the sines below lose precision
+        if cos > 0.9995 {
+            return Self::new(
+                self.w + t * (other.w - self.w),
+                self.v + t * (other.v - self.v),
+            )
+            .unit();
+        }
+
+        let theta = cos.acos();
+        let (a, b) = (((1. - t) * theta).sin(), (t * theta).sin());
+        let sin = theta.sin();
+
+        Self::new(
+            (a * self.w + b * other.w) / sin,
+            (a * self.v + b * other.v) / sin,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_rotation(a: &Quaternion, b: &Quaternion) {
+        // `q` and `-q` are the same rotation
+        assert!(a.dot(b).abs() > 1. - 1e-12, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn slerp_starts_and_ends_at_its_endpoints() {
+        let from_axis =
+            |axis: Vec3, degrees: f64| Quaternion::from_matrix(&Mat4::rotation(axis, degrees));
+        let pairs = [
+            (Quaternion::IDENTITY, from_axis(Vec3::new(0., 1., 0.), 90.)),
+            (
+                from_axis(Vec3::new(1., 2., 3.), 30.),
+                from_axis(Vec3::new(-1., 0., 1.), 250.),
+            ),
+            // Nearly parallel, interpolated linearly
+            (
+                from_axis(Vec3::new(0., 0., 1.), 10.),
+                from_axis(Vec3::new(0., 0., 1.), 10.5),
+            ),
+        ];
+
+        for (a, b) in pairs {
+            assert_same_rotation(&a.slerp(&b, 0.), &a);
+            assert_same_rotation(&a.slerp(&b, 1.), &b);
+        }
+    }
+
+    #[test]
+    fn slerp_halfway_takes_the_short_way() {
+        let a = Quaternion::IDENTITY;
+        let b = Quaternion::from_matrix(&Mat4::rotation(Vec3::new(0., 1., 0.), 300.));
+
+        // 300 degrees one way is 60 the other
+        let halfway = a.slerp(&b, 0.5);
+        assert!((a.angle_to(&halfway).to_degrees() - 30.).abs() < 1e-9);
+        assert!((halfway.angle_to(&b).to_degrees() - 30.).abs() < 1e-9);
+    }
+}
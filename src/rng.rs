@@ -0,0 +1,32 @@
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
+
+/// Random number generator used while rendering. Its output for a given seed is fixed
+/// across platforms and crate versions, so seeded renders can be compared bit for bit.
+pub type RenderRng = Pcg64Mcg;
+
+/// Generator for the samples of pixel `(x, y)`. Every pixel gets its own stream, so the
+/// image doesn't depend on the order pixels are rendered in, nor on the thread count.
+pub fn pixel_rng(seed: u64, x: u32, y: u32) -> RenderRng {
+    RenderRng::seed_from_u64(pixel_seed(seed, x, y))
+}
+
+/// Generator for sample number `sample` of pixel `(x, y)`. Unlike [`pixel_rng`], a render
+/// can be split into passes, or stopped and resumed, without changing its samples.
+pub fn sample_rng(seed: u64, x: u32, y: u32, sample: u64) -> RenderRng {
+    RenderRng::seed_from_u64(pixel_seed(seed, x, y) ^ splitmix64(!sample))
+}
+
+/// Seed of pixel `(x, y)` derived from the seed of the render.
+pub fn pixel_seed(seed: u64, x: u32, y: u32) -> u64 {
+    let pixel = (u64::from(y) << 32) | u64::from(x);
+    splitmix64(seed ^ splitmix64(pixel))
+}
+
+/// SplitMix64 finalizer, scrambles nearby inputs into unrelated outputs.
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
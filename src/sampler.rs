@@ -0,0 +1,588 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{pixel_seed, sample_rng, splitmix64, RenderRng, Result};
+
+/// Source of the random numbers of one sample of a pixel. Every call hands out the next
+/// dimension of the sample, so a path should ask for them in the same order on every
+/// sample: 2D pixel position, 2D lens position, then the numbers of each bounce.
+pub trait Sampler {
+    /// Next dimension, in `[0, 1)`.
+    fn get_1d(&mut self) -> f64;
+
+    /// Next two dimensions, in `[0, 1)^2`, well distributed together.
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+/// Way the samples of a pixel are spread out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerKind {
+    /// Independent uniform random numbers.
+    Independent,
+    /// One jittered sample per stratum of every dimension, with the strata shuffled
+    /// between dimensions.
+    Stratified,
+    /// Owen-scrambled Halton sequence.
+    Halton,
+    /// Owen-scrambled Sobol sequence, padded with independently shuffled 2D points.
+    #[default]
+    Sobol,
+}
+
+impl SamplerKind {
+    const NAMES: [(&'static str, Self); 4] = [
+        ("independent", Self::Independent),
+        ("stratified", Self::Stratified),
+        ("halton", Self::Halton),
+        ("sobol", Self::Sobol),
+    ];
+
+    /// Sampler for sample number `index` of pixel `(x, y)`, out of `samples_per_pixel`.
+    pub fn sampler(
+        &self,
+        seed: u64,
+        x: u32,
+        y: u32,
+        index: u64,
+        samples_per_pixel: u64,
+    ) -> PixelSampler {
+        match self {
+            SamplerKind::Independent => {
+                PixelSampler::Independent(IndependentSampler::new(seed, x, y, index))
+            }
+            SamplerKind::Stratified => PixelSampler::Stratified(StratifiedSampler::new(
+                seed,
+                x,
+                y,
+                index,
+                samples_per_pixel,
+            )),
+            SamplerKind::Halton => PixelSampler::Halton(HaltonSampler::new(seed, x, y, index)),
+            SamplerKind::Sobol => PixelSampler::Sobol(SobolSampler::new(seed, x, y, index)),
+        }
+    }
+}
+
+impl FromStr for SamplerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::NAMES.iter().find(|(name, _)| *name == s) {
+            Some((_, kind)) => Ok(*kind),
+            None => {
+                let names: Vec<_> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "unknown sampler `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Any of the samplers, picked at run time by [`SamplerKind`].
+#[derive(Debug, Clone)]
+pub enum PixelSampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+}
+
+impl Sampler for PixelSampler {
+    fn get_1d(&mut self) -> f64 {
+        match self {
+            PixelSampler::Independent(sampler) => sampler.get_1d(),
+            PixelSampler::Stratified(sampler) => sampler.get_1d(),
+            PixelSampler::Halton(sampler) => sampler.get_1d(),
+            PixelSampler::Sobol(sampler) => sampler.get_1d(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        match self {
+            PixelSampler::Independent(sampler) => sampler.get_2d(),
+            PixelSampler::Stratified(sampler) => sampler.get_2d(),
+            PixelSampler::Halton(sampler) => sampler.get_2d(),
+            PixelSampler::Sobol(sampler) => sampler.get_2d(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    rng: RenderRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64, x: u32, y: u32, index: u64) -> Self {
+        Self {
+            rng: sample_rng(seed, x, y, index),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+/// Splits every dimension into `samples_per_pixel` strata (a grid as close to square as
+/// possible for 2D) and gives each sample its own, in an order shuffled per dimension.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    seed: u64,
+    index: u64,
+    samples_per_pixel: u64,
+    grid: (u64, u64),
+    dimension: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, x: u32, y: u32, index: u64, samples_per_pixel: u64) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+
+        Self {
+            seed: pixel_seed(seed, x, y),
+            index,
+            samples_per_pixel,
+            grid: Self::grid(samples_per_pixel),
+            dimension: 0,
+        }
+    }
+
+    /// Columns and rows of the 2D strata of `count` samples. A grid with exactly `count`
+    /// cells is used if one isn't much longer than wide, else some cells stay empty.
+    fn grid(count: u64) -> (u64, u64) {
+        let nx = (count as f64).sqrt().ceil() as u64;
+        match (nx..=2 * nx).find(|columns| count % columns == 0) {
+            Some(columns) => (columns, count / columns),
+            None => (nx, count.div_ceil(nx)),
+        }
+    }
+
+    /// Stratum of this sample among `count`, and a jitter hash for it.
+    fn stratum(&mut self, count: u64) -> (u64, u64) {
+        let hash = mix(self.seed, self.dimension);
+        self.dimension += 1;
+
+        // Samples past the expected count are no longer stratified, but still uniform
+        let stratum = match self.index < count {
+            true => permutation_element(self.index, count, hash),
+            false => mix(hash, self.index) % count,
+        };
+
+        (stratum, mix(hash, !self.index))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn get_1d(&mut self) -> f64 {
+        let count = self.samples_per_pixel;
+        let (stratum, jitter) = self.stratum(count);
+
+        (stratum as f64 + to_unit(jitter)) / count as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (nx, ny) = self.grid;
+        let (stratum, jitter) = self.stratum(nx * ny);
+
+        (
+            ((stratum % nx) as f64 + to_unit(jitter)) / nx as f64,
+            ((stratum / nx) as f64 + to_unit(splitmix64(jitter))) / ny as f64,
+        )
+    }
+}
+
+/// Halton sequence, one prime base per dimension, Owen-scrambled per pixel and dimension.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    index: u64,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    /// Dimensions past this many use independent random numbers.
+    pub const MAX_DIMENSIONS: usize = PRIMES.len();
+
+    pub fn new(seed: u64, x: u32, y: u32, index: u64) -> Self {
+        Self {
+            seed: pixel_seed(seed, x, y),
+            index,
+            dimension: 0,
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let dimension = self.dimension;
+        let hash = mix(self.seed, dimension as u64);
+        self.dimension += 1;
+
+        match PRIMES.get(dimension) {
+            Some(&base) => scrambled_radical_inverse(base, self.index, hash),
+            None => to_unit(mix(hash, self.index)),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn get_1d(&mut self) -> f64 {
+        self.next()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.next(), self.next())
+    }
+}
+
+/// Owen-scrambled Sobol points, after Burley, "Practical Hash-based Owen Scrambling"
+/// (JCGT 2020). Every 1D or 2D request takes the first dimensions of the Sobol sequence
+/// with its own scrambling and shuffled sample order, which keeps them well stratified
+/// without needing direction numbers for hundreds of dimensions.
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    seed: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64, x: u32, y: u32, index: u64) -> Self {
+        Self {
+            seed: pixel_seed(seed, x, y),
+            index: index as u32,
+            dimension: 0,
+        }
+    }
+
+    /// Dimensions `0..N` of the shuffled sample, scrambled.
+    fn next<const N: usize>(&mut self) -> [f64; N] {
+        let hash = mix(self.seed, self.dimension);
+        self.dimension += 1;
+
+        let index = nested_uniform_scramble(self.index, hash as u32);
+        std::array::from_fn(|d| {
+            let seed = splitmix64(hash ^ d as u64) as u32;
+            sobol_to_unit(nested_uniform_scramble(sobol(index, d), seed))
+        })
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn get_1d(&mut self) -> f64 {
+        let [u] = self.next();
+        u
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let [u, v] = self.next();
+        (u, v)
+    }
+}
+
+/// Hash of `seed` and `value`.
+fn mix(seed: u64, value: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(value))
+}
+
+/// Top 53 bits of `bits` as a number in `[0, 1)`.
+fn to_unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1. / (1_u64 << 53) as f64)
+}
+
+fn sobol_to_unit(bits: u32) -> f64 {
+    bits as f64 * (1. / (1_u64 << 32) as f64)
+}
+
+/// Element `i` of a random permutation of `0..len` picked by `seed`, after Kensler,
+/// "Correlated Multi-Jittered Sampling" (2013).
+fn permutation_element(i: u64, len: u64, seed: u64) -> u64 {
+    // The hash works on 32 bits, larger strata counts fall back on a plain shift
+    if len > u64::from(u32::MAX) {
+        return (i + seed) % len;
+    }
+
+    let (l, p) = (len as u32, seed as u32);
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    let mut i = i as u32;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < l {
+            break;
+        }
+    }
+
+    // Summed in 64 bits: 2^32 isn't a multiple of `l`, so wrapping would repeat elements
+    (u64::from(i) + u64::from(p)) % u64::from(l)
+}
+
+/// Digits of `index` in `base` mirrored around the decimal point, with Owen scrambling:
+/// every digit is permuted by a random permutation that depends on `seed` and on the
+/// digits before it. Trailing zero digits are permuted too, so the points don't all pile
+/// up on the lower end.
+fn scrambled_radical_inverse(base: u32, mut index: u64, seed: u64) -> f64 {
+    let base = u64::from(base);
+    let inv_base = 1. / base as f64;
+    let mut inv_base_m = 1.;
+    let mut reversed_digits = 0_u64;
+
+    for position in 0.. {
+        if inv_base_m * inv_base < f64::EPSILON {
+            break;
+        }
+
+        let hash = mix(seed ^ reversed_digits, position);
+        let digit = permutation_element(index % base, base, hash);
+        index /= base;
+
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_m *= inv_base;
+    }
+
+    (reversed_digits as f64 * inv_base_m).min(1. - f64::EPSILON / 2.)
+}
+
+/// Primes used as the bases of [`HaltonSampler`], one per dimension.
+const PRIMES: [u32; 64] = primes();
+
+const fn primes<const N: usize>() -> [u32; N] {
+    let mut primes = [0; N];
+    let mut count = 0;
+    let mut candidate = 2;
+
+    while count < N {
+        let mut i = 0;
+        while i < count && candidate % primes[i] != 0 {
+            i += 1;
+        }
+        if i == count {
+            primes[count] = candidate;
+            count += 1;
+        }
+        candidate += 1;
+    }
+
+    primes
+}
+
+/// Direction numbers of the first two Sobol dimensions, the van der Corput sequence and
+/// the first primitive polynomial of Joe and Kuo.
+const SOBOL_DIRECTIONS: [[u32; 32]; 2] =
+    [sobol_directions(0, 0, &[]), sobol_directions(1, 0, &[1])];
+
+/// Direction numbers of a dimension with a primitive polynomial of `degree`, inner
+/// coefficients `a` and initial numbers `m`. Degree 0 is the van der Corput sequence.
+const fn sobol_directions(degree: usize, a: u32, m: &[u32]) -> [u32; 32] {
+    let mut v = [0; 32];
+    let mut k = 0;
+
+    while k < 32 {
+        v[k] = if degree == 0 {
+            1 << (31 - k)
+        } else if k < degree {
+            m[k] << (31 - k)
+        } else {
+            let mut value = v[k - degree] ^ (v[k - degree] >> degree);
+            let mut j = 1;
+            while j < degree {
+                if (a >> (degree - 1 - j)) & 1 == 1 {
+                    value ^= v[k - j];
+                }
+                j += 1;
+            }
+            value
+        };
+        k += 1;
+    }
+
+    v
+}
+
+fn sobol(index: u32, dimension: usize) -> u32 {
+    let mut x = 0;
+    let mut index = index;
+    let mut bit = 0;
+
+    while index != 0 {
+        if index & 1 == 1 {
+            x ^= SOBOL_DIRECTIONS[dimension][bit];
+        }
+        index >>= 1;
+        bit += 1;
+    }
+
+    x
+}
+
+/// Owen scrambling of the bits of `x`, most significant first: every bit is flipped
+/// depending on the bits above it.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash in which every bit only depends on the bits below it.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 42;
+
+    fn kinds() -> impl Iterator<Item = SamplerKind> {
+        SamplerKind::NAMES.into_iter().map(|(_, kind)| kind)
+    }
+
+    /// First `dimensions` 1D and 2D numbers of sample `index`, alternating.
+    fn sequence(kind: SamplerKind, index: u64, spp: u64, dimensions: usize) -> Vec<f64> {
+        let mut sampler = kind.sampler(SEED, 3, 7, index, spp);
+        (0..dimensions)
+            .flat_map(|_| {
+                let u = sampler.get_1d();
+                let (v, w) = sampler.get_2d();
+                [u, v, w]
+            })
+            .collect()
+    }
+
+    /// Whether `points` has exactly one point in every cell of every `2^a` by `2^b` grid
+    /// with `2^(a + b)` cells, `points.len()` being a power of two.
+    fn one_per_stratum(points: &[(f64, f64)]) -> bool {
+        let k = points.len().trailing_zeros();
+        (0..=k).all(|a| {
+            let (nx, ny) = (1_usize << a, 1_usize << (k - a));
+            let mut cells = vec![0; points.len()];
+            for &(u, v) in points {
+                let cell = (v * ny as f64) as usize * nx + (u * nx as f64) as usize;
+                cells[cell] += 1;
+            }
+            cells.iter().all(|&count| count == 1)
+        })
+    }
+
+    #[test]
+    fn samples_lie_in_the_unit_interval() {
+        // Past the sample count and the Halton dimensions too
+        let spp = 16;
+        for kind in kinds() {
+            for index in 0..2 * spp {
+                let dimensions = HaltonSampler::MAX_DIMENSIONS + 8;
+                for u in sequence(kind, index, spp, dimensions) {
+                    assert!((0. ..1.).contains(&u), "{kind:?} gave {u}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        for kind in kinds() {
+            for index in 0..8 {
+                assert_eq!(sequence(kind, index, 8, 10), sequence(kind, index, 8, 10));
+            }
+
+            let other = kind.sampler(SEED + 1, 3, 7, 0, 8).get_1d();
+            assert_ne!(sequence(kind, 0, 8, 1)[0], other, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn stratified_puts_one_sample_per_stratum() {
+        for k in 0..=10 {
+            let spp = 1 << k;
+            let (nx, ny) = StratifiedSampler::grid(spp);
+            assert_eq!(nx * ny, spp);
+
+            for dimension in 0..3 {
+                let mut cells = vec![0; spp as usize];
+                for index in 0..spp {
+                    let mut sampler = StratifiedSampler::new(SEED, 3, 7, index, spp);
+                    for _ in 0..dimension {
+                        sampler.get_2d();
+                    }
+                    let (u, v) = sampler.get_2d();
+                    let cell = (v * ny as f64) as u64 * nx + (u * nx as f64) as u64;
+                    cells[cell as usize] += 1;
+                }
+                assert!(cells.iter().all(|&count| count == 1), "{spp} samples");
+            }
+        }
+    }
+
+    #[test]
+    fn sobol_puts_one_sample_per_stratum_in_every_block() {
+        for k in 0..=8 {
+            let len = 1_u64 << k;
+            for block in 0..4 {
+                for dimension in 0..3 {
+                    let points: Vec<_> = (block * len..(block + 1) * len)
+                        .map(|index| {
+                            let mut sampler = SobolSampler::new(SEED, 3, 7, index);
+                            for _ in 0..dimension {
+                                sampler.get_2d();
+                            }
+                            sampler.get_2d()
+                        })
+                        .collect();
+                    assert!(one_per_stratum(&points), "block {block} of {len}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn permutation_element_is_a_bijection() {
+        for len in 1..=300 {
+            for seed in [0, 1, 0xdead_beef, u64::MAX] {
+                let mut elements: Vec<_> = (0..len)
+                    .map(|i| permutation_element(i, len, seed))
+                    .collect();
+                elements.sort_unstable();
+                assert!(elements.into_iter().eq(0..len), "len {len}, seed {seed}");
+            }
+        }
+    }
+}
@@ -0,0 +1,713 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, bail, Context};
+use rand::SeedableRng;
+use serde::Deserialize;
+
+use crate::{
+    load_obj_meshes, Aabb, AdaptiveSampling, AnimatedTransform, Aov, Background, Bvh, Camera,
+    CheckerTexture, Color, ConstantMedium, Denoiser, Filter, FilterKind, Fog, GridMedium, Hittable,
+    HittableList, Image, ImageTexture, Instance, IntegratorKind, Mat4, Material, NoisePattern,
+    NoiseTexture, OutputTransform, Perlin, PhaseFunction, Point3, RenderRng, Result, SamplerKind,
+    SolidColor, Sphere, SurfaceId, TextureObj, Transform, TriangleMesh, Vec3, VoxelGrid, World,
+};
+
+/// Declarative scene description, usually read from a TOML file with [`Scene::load`].
+///
+/// ```toml
+/// [image]
+/// width = 400
+/// aspect_ratio = 1.5
+///
+/// [render]
+/// samples_per_pixel = 50
+/// max_depth = 20
+///
+/// [camera]
+/// vfov = 30
+/// lookfrom = [0, 1, 5]
+/// lookat = [0, 0.5, 0]
+///
+/// [output]
+/// exposure = 0.5
+/// tone_map = "aces"
+///
+/// [materials.ground]
+/// type = "lambertian"
+/// albedo = [0.5, 0.5, 0.5]
+///
+/// [[objects]]
+/// type = "sphere"
+/// center = [0, -1000, 0]
+/// radius = 1000
+/// material = "ground"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default)]
+    pub image: ImageSettings,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub camera: CameraSettings,
+    /// Exposure and tone mapping of 8-bit outputs.
+    #[serde(default)]
+    pub output: OutputTransform,
+    #[serde(default)]
+    pub textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    pub materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    pub fog: Option<FogSettings>,
+    /// Directory that relative paths in the scene (e.g. meshes) are resolved against.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageSettings {
+    pub width: u32,
+    pub aspect_ratio: f64,
+    /// Height in pixels, the one given by `aspect_ratio` when absent.
+    pub height: Option<u32>,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            aspect_ratio: 16. / 9.,
+            height: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub samples_per_pixel: i64,
+    /// Most bounces of a path, only ended by Russian roulette when absent.
+    pub max_depth: Option<u16>,
+    /// Uniform background colour, the sky gradient when absent.
+    pub background: Option<Color>,
+    /// Auxiliary passes rendered along with the image.
+    pub aovs: Vec<Aov>,
+    /// Whether to run the [`Denoiser`] on the rendered image.
+    pub denoise: bool,
+    /// Spreads the samples by the noise of each pixel, `samples_per_pixel` is then the
+    /// average over the image.
+    pub adaptive: Option<AdaptiveSampling>,
+    /// How the samples of each pixel are spread out.
+    pub sampler: SamplerKind,
+    /// Pixel reconstruction filter.
+    pub filter: FilterKind,
+    /// Radius of the filter in pixels, the usual one for the filter when absent.
+    pub filter_radius: Option<f64>,
+    /// Light transport algorithm.
+    pub integrator: IntegratorKind,
+    /// How far ambient occlusion looks for occluders, unbounded when absent.
+    pub ao_distance: Option<f64>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            samples_per_pixel: 10,
+            max_depth: None,
+            background: None,
+            aovs: vec![],
+            denoise: false,
+            adaptive: None,
+            sampler: SamplerKind::default(),
+            filter: FilterKind::default(),
+            filter_radius: None,
+            integrator: IntegratorKind::default(),
+            ao_distance: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraSettings {
+    pub vfov: f64,
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub defocus_angle: f64,
+    pub focus_dist: f64,
+    /// Times the shutter opens and closes at, objects move from time 0 to time 1.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            vfov: 90.,
+            lookfrom: Point3::ZERO,
+            lookat: Point3::NEG_Z,
+            vup: Vec3::Y,
+            defocus_angle: 0.,
+            focus_dist: 10.,
+            shutter_open: 0.,
+            shutter_close: 0.,
+        }
+    }
+}
+
+/// Either a constant colour, `[r, g, b]`, or the name of an entry of `[textures]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TextureRef {
+    Color(Color),
+    Named(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDesc {
+    Solid {
+        color: Color,
+    },
+    /// 3D checkerboard with cubes of side `scale`.
+    Checker {
+        scale: f64,
+        even: TextureRef,
+        odd: TextureRef,
+    },
+    /// Image file, relative to the scene file.
+    Image {
+        path: PathBuf,
+    },
+    /// Perlin noise pattern, the same `seed` always gives the same pattern.
+    Noise {
+        #[serde(default = "default_pattern")]
+        pattern: NoisePattern,
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default = "default_noise_color")]
+        color: Color,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+const fn default_pattern() -> NoisePattern {
+    NoisePattern::Noise
+}
+
+const fn default_scale() -> f64 {
+    1.
+}
+
+const fn default_noise_color() -> Color {
+    Color::ONE
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDesc {
+    Lambertian {
+        albedo: TextureRef,
+    },
+    Metal {
+        albedo: TextureRef,
+        fuzz: f64,
+    },
+    Dielectric {
+        ior: f64,
+    },
+    DiffuseLight {
+        color: TextureRef,
+        #[serde(default = "default_strength")]
+        strength: f64,
+    },
+    /// Participating medium: objects of this material are filled with it, see
+    /// [`ConstantMedium`], instead of having a surface.
+    Volume {
+        albedo: TextureRef,
+        /// Density of the medium, or the scale of the densities of a grid.
+        density: f64,
+        /// Asymmetry of the [`PhaseFunction`], 0 for isotropic scattering.
+        #[serde(default)]
+        anisotropy: f64,
+    },
+}
+
+const fn default_strength() -> f64 {
+    1.
+}
+
+/// `[fog]`: medium filling the scene up to `distance` from the camera, see [`Fog`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FogSettings {
+    pub density: f64,
+    #[serde(default = "default_fog_albedo")]
+    pub albedo: Color,
+    /// Asymmetry of the [`PhaseFunction`], 0 for isotropic scattering.
+    #[serde(default)]
+    pub anisotropy: f64,
+    /// Radius of the ball around the camera the fog fills.
+    pub distance: f64,
+}
+
+const fn default_fog_albedo() -> Color {
+    Color::ONE
+}
+
+/// Entry of `[[objects]]`: a shape, placed in the world by an optional transform.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectDesc {
+    #[serde(flatten)]
+    pub shape: ShapeDesc,
+    /// Steps applied to the shape in order, e.g. scaling it, then rotating it, then
+    /// moving it into place.
+    #[serde(default)]
+    pub transform: Vec<TransformDesc>,
+    /// Steps placing the shape at time 1, it moves there from `transform` at time 0.
+    pub end_transform: Option<Vec<TransformDesc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ShapeDesc {
+    /// Sphere at `center`, moving to `end_center` by time 1 when given.
+    Sphere {
+        center: Point3,
+        end_center: Option<Point3>,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [Point3; 3],
+        material: String,
+    },
+    /// Parallelogram with corner `corner` and edges `u` and `v`.
+    Quad {
+        corner: Point3,
+        u: Vec3,
+        v: Vec3,
+        material: String,
+    },
+    /// Axis-aligned box between two opposite corners.
+    Box {
+        min: Point3,
+        max: Point3,
+        material: String,
+    },
+    /// Wavefront `.obj` file, its materials come from the `.mtl` libraries it references.
+    Mesh { path: PathBuf },
+    /// Medium of varying density filling the box between `min` and `max`, see
+    /// [`GridMedium`]. The values of the voxel file `density`, see [`VoxelGrid::load`], are
+    /// scaled by the density of the material, which must be a volume. With a `temperature`
+    /// file in kelvins the medium glows like a black body, `emission` times as bright.
+    Grid {
+        density: PathBuf,
+        temperature: Option<PathBuf>,
+        /// Resolution of the `.raw` voxel files, text ones give their own.
+        resolution: Option<[usize; 3]>,
+        min: Point3,
+        max: Point3,
+        #[serde(default = "default_strength")]
+        emission: f64,
+        material: String,
+    },
+}
+
+impl ShapeDesc {
+    /// Material of the surface of the shape. Grids have none, they are media already.
+    fn material(&self) -> Option<&str> {
+        match self {
+            ShapeDesc::Sphere { material, .. }
+            | ShapeDesc::Triangle { material, .. }
+            | ShapeDesc::Quad { material, .. }
+            | ShapeDesc::Box { material, .. } => Some(material),
+            ShapeDesc::Mesh { .. } | ShapeDesc::Grid { .. } => None,
+        }
+    }
+
+    const fn kind(&self) -> &'static str {
+        match self {
+            ShapeDesc::Sphere { .. } => "sphere",
+            ShapeDesc::Triangle { .. } => "triangle",
+            ShapeDesc::Quad { .. } => "quad",
+            ShapeDesc::Box { .. } => "box",
+            ShapeDesc::Mesh { .. } => "mesh",
+            ShapeDesc::Grid { .. } => "grid",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformDesc {
+    Translate(Vec3),
+    /// Scale factors along the axes.
+    Scale(Vec3),
+    /// Counterclockwise rotation around `axis`, through the origin.
+    Rotate {
+        axis: Vec3,
+        degrees: f64,
+    },
+    /// Affine matrix, row by row.
+    Matrix([[f64; 4]; 4]),
+}
+
+impl TransformDesc {
+    /// Transform applying `steps` in order.
+    fn transform(steps: &[Self]) -> Result<Transform> {
+        let matrix = steps
+            .iter()
+            .fold(Mat4::IDENTITY, |matrix, step| step.matrix() * matrix);
+
+        Transform::new(matrix)
+    }
+
+    fn matrix(&self) -> Mat4 {
+        match self {
+            TransformDesc::Translate(offset) => Mat4::translation(*offset),
+            TransformDesc::Scale(factors) => Mat4::scaling(*factors),
+            TransformDesc::Rotate { axis, degrees } => Mat4::rotation(*axis, *degrees),
+            TransformDesc::Matrix(rows) => Mat4::new(*rows),
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("failed to read scene {}", path.display()))?;
+
+        let mut scene: Self =
+            toml::from_str(&source).with_context(|| format!("invalid scene {}", path.display()))?;
+        scene.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        Ok(scene)
+    }
+
+    pub fn camera(&self) -> Camera {
+        let img = match self.image.height {
+            Some(height) => Image::with_height(self.image.width, height),
+            None => Image::new(self.image.width, self.image.aspect_ratio),
+        };
+        let cam = &self.camera;
+
+        let filter = Filter::new(self.render.filter);
+        let filter = match self.render.filter_radius {
+            Some(radius) => filter.with_radius(radius),
+            None => filter,
+        };
+
+        let background = self
+            .render
+            .background
+            .map_or(Background::Sky, Background::Solid);
+
+        Camera::new(
+            img,
+            self.render.samples_per_pixel,
+            self.render.max_depth,
+            cam.vfov,
+            cam.lookfrom,
+            cam.lookat,
+            cam.vup,
+            cam.defocus_angle,
+            cam.focus_dist,
+        )
+        .with_shutter(cam.shutter_open, cam.shutter_close)
+        .with_background(background)
+        .with_aovs(self.render.aovs.iter().copied())
+        .with_denoiser(self.render.denoise.then(Denoiser::default))
+        .with_adaptive_sampling(self.render.adaptive)
+        .with_sampler(self.render.sampler)
+        .with_filter(filter)
+        .with_integrator(
+            self.render
+                .integrator
+                .integrator(self.render.max_depth, self.render.ao_distance),
+        )
+    }
+
+    /// Builds the objects of the scene, each one an instance in the top-level BVH. Object
+    /// `i` of `objects` gets ID `i + 1` and the materials of `materials` are numbered from 1
+    /// in name order, followed by the ones of mesh files, see [`SurfaceId`].
+    pub fn world(&self) -> Result<World> {
+        let mut textures = HashMap::new();
+        let mut materials = HashMap::new();
+
+        let mut names: Vec<&String> = self.materials.keys().collect();
+        names.sort_unstable();
+
+        for (id, name) in (1..).zip(names) {
+            let material = self
+                .material(&self.materials[name], &mut textures)
+                .with_context(|| format!("material `{}`", name))?;
+            materials.insert(name.as_str(), (material, id));
+        }
+
+        let lookup_material = |name: &str| {
+            materials.get(name).cloned().ok_or_else(|| {
+                anyhow!(
+                    "unknown material `{}`, expected one of: {}",
+                    name,
+                    Self::known_names(&self.materials)
+                )
+            })
+        };
+
+        let mut instances = vec![];
+        let mut next_material_id = materials.len() as u32 + 1;
+        // Every mesh file is loaded once, all the objects using it share its BVH
+        let mut meshes: HashMap<&Path, Arc<dyn Hittable>> = HashMap::new();
+
+        for (object_id, object) in (1..).zip(&self.objects) {
+            let context = || format!("object #{} ({})", object_id, object.shape.kind());
+            let id = |material| SurfaceId {
+                object: object_id,
+                material,
+            };
+
+            let transform = TransformDesc::transform(&object.transform).with_context(context)?;
+            let transform = match &object.end_transform {
+                Some(end) => AnimatedTransform::new(
+                    transform,
+                    TransformDesc::transform(end).with_context(context)?,
+                ),
+                None => transform.into(),
+            };
+
+            let shape: Arc<dyn Hittable> = match &object.shape {
+                ShapeDesc::Sphere {
+                    center,
+                    end_center,
+                    radius,
+                    material,
+                } => {
+                    let (material, material_id) =
+                        lookup_material(material).with_context(context)?;
+                    let end = end_center.unwrap_or(*center);
+                    Arc::new(
+                        Sphere::moving(*center, end, *radius, material).with_id(id(material_id)),
+                    )
+                }
+                ShapeDesc::Triangle { vertices, material } => {
+                    let (material, material_id) =
+                        lookup_material(material).with_context(context)?;
+                    let mesh = TriangleMesh::new(vertices.to_vec(), vec![[0, 1, 2]], material)?;
+                    Arc::new(Bvh::new(mesh.with_id(id(material_id)).into_hittables()))
+                }
+                ShapeDesc::Quad {
+                    corner,
+                    u,
+                    v,
+                    material,
+                } => {
+                    let (material, material_id) =
+                        lookup_material(material).with_context(context)?;
+                    let mesh = TriangleMesh::quad(*corner, *u, *v, material);
+                    Arc::new(Bvh::new(mesh.with_id(id(material_id)).into_hittables()))
+                }
+                ShapeDesc::Box { min, max, material } => {
+                    let (material, material_id) =
+                        lookup_material(material).with_context(context)?;
+                    let mesh = TriangleMesh::cuboid(*min, *max, material);
+                    Arc::new(Bvh::new(mesh.with_id(id(material_id)).into_hittables()))
+                }
+                ShapeDesc::Mesh { path } => match meshes.get(&**path) {
+                    Some(mesh) => mesh.clone(),
+                    None => {
+                        // A material used by several runs of faces keeps one ID
+                        let mut material_ids: HashMap<String, u32> = HashMap::new();
+                        let mut triangles = HittableList::new();
+                        for (name, mesh) in
+                            load_obj_meshes(self.base_dir.join(path)).with_context(context)?
+                        {
+                            let material_id = *material_ids.entry(name).or_insert_with(|| {
+                                next_material_id += 1;
+                                next_material_id - 1
+                            });
+                            triangles.extend(mesh.with_id(id(material_id)).into_hittables());
+                        }
+
+                        let mesh: Arc<dyn Hittable> = Arc::new(Bvh::new(triangles));
+                        meshes.insert(path, mesh.clone());
+                        mesh
+                    }
+                },
+                ShapeDesc::Grid {
+                    density,
+                    temperature,
+                    resolution,
+                    min,
+                    max,
+                    emission,
+                    material: name,
+                } => {
+                    let (material, material_id) = lookup_material(name).with_context(context)?;
+                    let Some(MaterialDesc::Volume { density: scale, .. }) =
+                        self.materials.get(name)
+                    else {
+                        return Err(anyhow!("material `{}` isn't a volume", name))
+                            .with_context(context);
+                    };
+
+                    let load = |path: &PathBuf| {
+                        VoxelGrid::load(self.base_dir.join(path), *resolution).with_context(context)
+                    };
+                    let medium = GridMedium::new(
+                        Aabb::from_points(*min, *max),
+                        load(density)?,
+                        *scale,
+                        material,
+                    )
+                    .with_id(id(material_id));
+                    match temperature {
+                        Some(path) => Arc::new(medium.with_temperature(load(path)?, *emission)),
+                        None => Arc::new(medium),
+                    }
+                }
+            };
+
+            // Objects of a volume material are filled with it, the shape only bounds it
+            let shape = match object.shape.material() {
+                Some(name) => match self.materials.get(name) {
+                    Some(MaterialDesc::Volume { density, .. }) => {
+                        let (material, _) = lookup_material(name)?;
+                        Arc::new(ConstantMedium::new(shape, *density, material))
+                    }
+                    _ => shape,
+                },
+                None => shape,
+            };
+
+            instances.push(Instance::new(shape, transform).with_object_id(object_id));
+        }
+
+        let world = World::from_instances(instances);
+        Ok(match &self.fog {
+            Some(fog) => world.with_fog(Fog::new(
+                fog.density,
+                fog.albedo,
+                PhaseFunction::new(fog.anisotropy),
+                self.camera.lookfrom,
+                fog.distance,
+            )),
+            None => world,
+        })
+    }
+
+    pub fn build(&self) -> Result<(Camera, World)> {
+        Ok((self.camera(), self.world()?))
+    }
+
+    fn material(
+        &self,
+        desc: &MaterialDesc,
+        textures: &mut HashMap<String, TextureObj>,
+    ) -> Result<Material> {
+        Ok(match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                Material::Lambertian(self.texture(albedo, textures, &mut vec![])?)
+            }
+            MaterialDesc::Metal { albedo, fuzz } => Material::Metal {
+                albedo: self.texture(albedo, textures, &mut vec![])?,
+                fuzz: *fuzz,
+            },
+            MaterialDesc::Dielectric { ior } => Material::Dialectric(*ior),
+            MaterialDesc::DiffuseLight { color, strength } => Material::DiffuseLight {
+                emit: self.texture(color, textures, &mut vec![])?,
+                strength: *strength,
+            },
+            MaterialDesc::Volume {
+                albedo, anisotropy, ..
+            } => Material::Volume {
+                albedo: self.texture(albedo, textures, &mut vec![])?,
+                phase: PhaseFunction::new(*anisotropy),
+            },
+        })
+    }
+
+    /// Resolves a texture reference, building named textures once and caching them in
+    /// `textures`. `pending` holds the names being built, to report reference cycles.
+    fn texture(
+        &self,
+        texture: &TextureRef,
+        textures: &mut HashMap<String, TextureObj>,
+        pending: &mut Vec<String>,
+    ) -> Result<TextureObj> {
+        let name = match texture {
+            TextureRef::Color(color) => return Ok(Arc::new(SolidColor::new(*color))),
+            TextureRef::Named(name) => name,
+        };
+
+        if let Some(texture) = textures.get(name) {
+            return Ok(texture.clone());
+        }
+
+        if pending.contains(name) {
+            bail!(
+                "textures reference each other in a cycle: {} -> {}",
+                pending.join(" -> "),
+                name
+            );
+        }
+
+        let desc = self.textures.get(name).ok_or_else(|| {
+            anyhow!(
+                "unknown texture `{}`, expected one of: {}",
+                name,
+                Self::known_names(&self.textures)
+            )
+        })?;
+
+        pending.push(name.clone());
+        let texture: TextureObj = match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new(*color)),
+            TextureDesc::Checker { scale, even, odd } => Arc::new(CheckerTexture::new(
+                *scale,
+                self.texture(even, textures, pending)?,
+                self.texture(odd, textures, pending)?,
+            )),
+            TextureDesc::Image { path } => Arc::new(
+                ImageTexture::load(self.base_dir.join(path))
+                    .with_context(|| format!("texture `{}`", name))?,
+            ),
+            TextureDesc::Noise {
+                pattern,
+                scale,
+                color,
+                seed,
+            } => {
+                let noise = Perlin::new(&mut RenderRng::seed_from_u64(*seed));
+                Arc::new(NoiseTexture::new(noise, *scale, *pattern, *color))
+            }
+        };
+        pending.pop();
+
+        textures.insert(name.clone(), texture.clone());
+        Ok(texture)
+    }
+
+    fn known_names<T>(entries: &HashMap<String, T>) -> String {
+        let mut known: Vec<&str> = entries.keys().map(String::as_str).collect();
+        known.sort_unstable();
+        known.join(", ")
+    }
+}
+
+/// Reads a scene file and builds its camera and world.
+pub fn load_scene(path: impl AsRef<Path>) -> Result<(Camera, World)> {
+    Scene::load(path)?.build()
+}
@@ -1,53 +1,174 @@
-use crate::{HitRecord, HitResult, Hittable, Interval, Material, Point3, Ray};
+use crate::{
+    Aabb, HitRecord, HitResult, Hittable, Interval, Material, Onb, Point3, Ray, SurfaceId, Vec3,
+    INFINITY, PI,
+};
 
+/// Sphere, optionally moving in a straight line over the shutter, see [`Sphere::moving`].
 #[derive(Clone)]
 pub struct Sphere {
+    /// Centre at time 0.
     center: Point3,
+    /// Distance the centre moves by time 1.
+    motion: Vec3,
     radius: f64,
     material: Material,
+    id: SurfaceId,
 }
 
 impl Sphere {
     pub const fn new(center: Point3, radius: f64, material: Material) -> Self {
+        Self::moving(center, center, radius, material)
+    }
+
+    /// Sphere moving from `start` at time 0 to `end` at time 1. It stays at either end
+    /// outside of that interval.
+    pub const fn moving(start: Point3, end: Point3, radius: f64, material: Material) -> Self {
         Self {
-            center,
+            center: start,
+            motion: Vec3::new(
+                end.x() - start.x(),
+                end.y() - start.y(),
+                end.z() - start.z(),
+            ),
             radius,
             material,
+            id: SurfaceId {
+                object: 0,
+                material: 0,
+            },
         }
     }
+
+    /// Identifiers reported in the object and material ID passes.
+    pub const fn with_id(mut self, id: SurfaceId) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Centre at `time`.
+    pub fn center(&self, time: f64) -> Point3 {
+        self.center + time.clamp(0., 1.) * self.motion
+    }
+
+    /// Cosine of the half angle of the cone the sphere subtends from `origin`, `None`
+    /// from inside the sphere.
+    fn cos_theta_max(&self, origin: Point3, center: Point3) -> Option<f64> {
+        let distance_squared = (center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        (distance_squared > radius_squared).then(|| (1. - radius_squared / distance_squared).sqrt())
+    }
+
+    /// Spherical coordinates of a point on the unit sphere, with `u` going around the y axis
+    /// starting from -x and `v` going from -y to +y.
+    fn uv(p: Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (phi / (2. * PI), theta / PI)
+    }
+
+    /// Derivatives of the surface position along the coordinates of [`Sphere::uv`],
+    /// for the unit normal `n`.
+    fn derivatives(&self, n: Vec3) -> (Vec3, Vec3) {
+        // Clamped so that the poles, where `u` is undefined, still get a usable frame
+        let sin_theta = (1. - n.y() * n.y()).max(0.).sqrt().max(1e-8);
+
+        let dpdu = 2. * PI * self.radius * Vec3::new(n.z(), 0., -n.x());
+        let dpdv = PI
+            * self.radius
+            * Vec3::new(
+                -n.y() * n.x() / sin_theta,
+                sin_theta,
+                -n.y() * n.z() / sin_theta,
+            );
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult {
-        let oc = ray.origin() - self.center;
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        let center = self.center(ray.time());
+        let oc = ray.origin() - center;
         let a = ray.direction().length_squared();
         let half_b = oc.dot(ray.direction());
         let c = oc.length_squared() - self.radius * self.radius;
         let discriminant = half_b * half_b - a * c;
 
-        match discriminant < 0. {
-            true => return None,
-            false => (),
-        };
+        if discriminant < 0. {
+            return None;
+        }
 
         let sqrtd = discriminant.sqrt();
         let mut root = (-half_b - sqrtd) / a;
 
-        match !ray_t.surrounds(root) {
-            true => {
-                root = (-half_b + sqrtd) / a;
-                match !ray_t.surrounds(root) {
-                    true => return None,
-                    false => (),
-                }
+        if !ray_t.surrounds(root) {
+            root = (-half_b + sqrtd) / a;
+            if !ray_t.surrounds(root) {
+                return None;
             }
-            false => (),
-        };
+        }
 
         let t = root;
         let p = ray.at(t);
-        let outward_normal = (p - self.center) / self.radius;
+        let outward_normal = (p - center) / self.radius;
+
+        let (u, v) = Self::uv(outward_normal);
+        let (dpdu, dpdv) = self.derivatives(outward_normal);
+
+        Some(
+            HitRecord::new(p, t, ray, outward_normal, &self.material)
+                .with_surface_coords(u, v, dpdu, dpdv)
+                .with_id(self.id)
+                .with_object(self),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::splat(self.radius);
+        let end = self.center + self.motion;
+
+        Aabb::from_points(self.center - rvec, self.center + rvec)
+            .union(&Aabb::from_points(end - rvec, end + rvec))
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // Directions are picked uniformly in the cone the sphere subtends, which is empty from
+    // inside the sphere
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        if self.hit(ray, &Interval::new(0.001, INFINITY)).is_none() {
+            return 0.;
+        }
+
+        match self.cos_theta_max(ray.origin(), self.center(ray.time())) {
+            Some(cos_theta_max) => 1. / (2. * PI * (1. - cos_theta_max)),
+            None => 0.,
+        }
+    }
+
+    fn random(&self, origin: Point3, time: f64, (u, v): (f64, f64)) -> Vec3 {
+        let center = self.center(time);
+        let Some(cos_theta_max) = self.cos_theta_max(origin, center) else {
+            return center - origin;
+        };
+
+        let to_center = center - origin;
+        let distance = to_center.length();
+        let cos_theta = 1. - u * (1. - cos_theta_max);
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * v;
+
+        // Distance along the sampled direction to the near side of the sphere
+        let t = distance * cos_theta
+            - (self.radius * self.radius - distance * distance * sin_theta * sin_theta)
+                .max(0.)
+                .sqrt();
 
-        Some(HitRecord::new(p, t, ray, outward_normal, self.material))
+        let local = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+        t * Onb::from_w(to_center).local(local)
     }
 }
@@ -0,0 +1,165 @@
+use std::{fmt::Debug, path::Path, sync::Arc};
+
+use anyhow::Context;
+use image::{ColorType, Rgb32FImage};
+use serde::Deserialize;
+
+use crate::{srgb_to_linear, Color, Interval, Perlin, Point3, Result};
+
+/// Colour that varies over a surface, looked up by surface coordinates `(u, v)` or
+/// by the hit point `p` itself for solid textures.
+pub trait Texture: Debug + Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+pub type TextureObj = Arc<dyn Texture>;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolidColor(Color);
+
+impl SolidColor {
+    pub const fn new(albedo: Color) -> Self {
+        Self(albedo)
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
+        self.0
+    }
+}
+
+/// Solid checkerboard alternating between two textures in cubes of side `scale`.
+#[derive(Debug, Clone)]
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: TextureObj,
+    odd: TextureObj,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: TextureObj, odd: TextureObj) -> Self {
+        Self {
+            inv_scale: 1. / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i64;
+        let y = (self.inv_scale * p.y()).floor() as i64;
+        let z = (self.inv_scale * p.z()).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Image mapped over `(u, v)` in [0, 1], with `v` pointing up.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    image: Rgb32FImage,
+}
+
+impl ImageTexture {
+    /// Loads an image in any format supported by the `image` crate. The colours of 8 and
+    /// 16-bit images are assumed to be sRGB encoded, as they are written by
+    /// [`crate::write_color`], while floating point ones (EXR, HDR) are already linear.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .with_context(|| format!("failed to load texture {}", path.display()))?;
+        let is_linear = matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+        let mut image = image.into_rgb32f();
+
+        if !is_linear {
+            for channel in image.iter_mut() {
+                *channel = srgb_to_linear(*channel as f64) as f32;
+            }
+        }
+
+        Ok(Self { image })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {
+            return Color::new(0., 1., 1.);
+        }
+
+        let unit = Interval::new(0., 1.);
+        let u = unit.clamp(u);
+        let v = 1. - unit.clamp(v);
+
+        let i = ((u * width as f64) as u32).min(width - 1);
+        let j = ((v * height as f64) as u32).min(height - 1);
+        let [r, g, b] = self.image.get_pixel(i, j).0;
+
+        Color::new(r as f64, g as f64, b as f64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoisePattern {
+    /// Single octave of Perlin noise.
+    Noise,
+    /// Several octaves of noise summed together.
+    Turbulence,
+    /// Stripes along z, phase shifted by turbulence.
+    Marble,
+}
+
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+    pattern: NoisePattern,
+    albedo: Color,
+}
+
+impl NoiseTexture {
+    const TURBULENCE_DEPTH: u32 = 7;
+
+    pub fn new(noise: Perlin, scale: f64, pattern: NoisePattern, albedo: Color) -> Self {
+        Self {
+            noise,
+            scale,
+            pattern,
+            albedo,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let intensity = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1. + self.noise.noise(self.scale * p)),
+            NoisePattern::Turbulence => self
+                .noise
+                .turbulence(self.scale * p, Self::TURBULENCE_DEPTH),
+            NoisePattern::Marble => {
+                let turbulence = self.noise.turbulence(p, Self::TURBULENCE_DEPTH);
+                0.5 * (1. + (self.scale * p.z() + 10. * turbulence).sin())
+            }
+        };
+
+        intensity * self.albedo
+    }
+}
@@ -0,0 +1,60 @@
+/// Rectangular block of pixels rendered as one unit of work.
+#[derive(Debug, Clone, Copy)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    pub const DEFAULT_SIZE: u32 = 32;
+
+    /// Splits a `width` x `height` image into row-major tiles of at most `size` x `size` pixels.
+    pub fn grid(width: u32, height: u32, size: u32) -> Vec<Self> {
+        let size = size.max(1);
+        let mut tiles = vec![];
+
+        for y in (0..height).step_by(size as usize) {
+            for x in (0..width).step_by(size as usize) {
+                tiles.push(Self {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                });
+            }
+        }
+
+        tiles
+    }
+
+    /// Tile grown by `margin` pixels on every side, clipped to a `width` x `height` image.
+    pub fn expand(&self, margin: u32, width: u32, height: u32) -> Self {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+
+        Self {
+            x,
+            y,
+            width: (self.x + self.width + margin).min(width) - x,
+            height: (self.y + self.height + margin).min(height) - y,
+        }
+    }
+
+    pub const fn pixel_count(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Pixel coordinates covered by the tile, in row-major order.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let Self {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+
+        (y..y + height).flat_map(move |j| (x..x + width).map(move |i| (i, j)))
+    }
+}
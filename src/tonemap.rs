@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use crate::{Color, Result};
+
+/// Curve compressing scene radiance into the displayable [0, 1] range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMap {
+    /// Plain clamp, everything brighter than 1 is white.
+    #[default]
+    None,
+    /// `c / (1 + c)`, never reaches white.
+    Reinhard,
+    /// Reinhard with a white point, radiance at or above `white` maps to 1.
+    ReinhardExtended,
+    /// John Hable's filmic curve from Uncharted 2.
+    Hable,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms.
+    Aces,
+}
+
+impl ToneMap {
+    const NAMES: [(&'static str, Self); 5] = [
+        ("none", Self::None),
+        ("reinhard", Self::Reinhard),
+        ("reinhard_extended", Self::ReinhardExtended),
+        ("hable", Self::Hable),
+        ("aces", Self::Aces),
+    ];
+
+    pub fn apply(&self, color: Color, white: f64) -> Color {
+        match self {
+            ToneMap::None => color,
+            ToneMap::Reinhard => map(color, |c| c / (1. + c)),
+            ToneMap::ReinhardExtended => {
+                let white_sq = white * white;
+                map(color, |c| c * (1. + c / white_sq) / (1. + c))
+            }
+            ToneMap::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.;
+                const WHITE: f64 = 11.2;
+                map(color, |c| hable(EXPOSURE_BIAS * c) / hable(WHITE))
+            }
+            ToneMap::Aces => aces_fitted(color),
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::NAMES.iter().find(|(name, _)| *name == s) {
+            Some((_, tone_map)) => Ok(*tone_map),
+            None => {
+                let names: Vec<_> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "unknown tone map `{}`, expected one of: {}",
+                    s,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Turns linear scene radiance into display-referred linear colour in [0, 1]:
+/// exposure, then a tone mapping curve. [`crate::write_color`] then encodes it to sRGB.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputTransform {
+    /// Exposure adjustment in stops, each stop doubles the radiance.
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    /// Radiance mapped to white by [`ToneMap::ReinhardExtended`].
+    pub white: f64,
+}
+
+impl Default for OutputTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.,
+            tone_map: ToneMap::None,
+            white: 4.,
+        }
+    }
+}
+
+impl OutputTransform {
+    pub fn apply(&self, color: Color) -> Color {
+        self.tone_map
+            .apply(self.exposure.exp2() * color, self.white)
+    }
+}
+
+fn map(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(color.x()), f(color.y()), f(color.z()))
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+fn aces_fitted(color: Color) -> Color {
+    // sRGB to the ACES rendering space, with the RRT saturation folded in
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT saturation and back to linear sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let rrt_and_odt_fit = |v: f64| {
+        let a = v * (v + 0.0245786) - 0.000090537;
+        let b = v * (0.983729 * v + 0.432951) + 0.238081;
+        a / b
+    };
+
+    let color = map(mul(&INPUT, color), rrt_and_odt_fit);
+    mul(&OUTPUT, color)
+}
+
+fn mul(m: &[[f64; 3]; 3], v: Color) -> Color {
+    let row = |r: &[f64; 3]| r[0] * v.x() + r[1] * v.y() + r[2] * v.z();
+    Color::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
@@ -0,0 +1,550 @@
+use std::ops::Mul;
+
+use anyhow::anyhow;
+
+use crate::{Aabb, Point3, Quaternion, Result, Vec3};
+
+/// Row-major 4x4 matrix, acting on column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self::new([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    pub const fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub const fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1., 0., 0., offset.x()],
+            [0., 1., 0., offset.y()],
+            [0., 0., 1., offset.z()],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub const fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0., 0., 0.],
+            [0., factors.y(), 0., 0.],
+            [0., 0., factors.z(), 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// Counterclockwise rotation by `degrees` around `axis`, looking down the axis.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let t = 1. - cos;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+
+        Self::new([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub const fn rows(&self) -> &[[f64; 4]; 4] {
+        &self.m
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self::new(m)
+    }
+
+    /// Inverse by Gauss-Jordan elimination with partial pivoting, `None` for a singular
+    /// matrix.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1. / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in (0..4).filter(|&row| row != col) {
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Self::new(inv))
+    }
+
+    /// Determinant of the upper left 3x3 block, the factor volumes are scaled by.
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Applies the matrix to a point, dividing by `w` for projective matrices.
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * p.x() + m[i][1] * p.y() + m[i][2] * p.z() + m[i][3];
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        if w == 1. {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+
+    /// Applies the matrix to a direction, ignoring the translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        let row = |i: usize| m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Self::new(m)
+    }
+}
+
+/// Affine transform from an object's own space to world space, along with its inverse.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        matrix: Mat4::IDENTITY,
+        inverse: Mat4::IDENTITY,
+    };
+
+    /// Transform applying `matrix`, which must be invertible.
+    pub fn new(matrix: Mat4) -> Result<Self> {
+        let inverse = matrix
+            .inverse()
+            .ok_or_else(|| anyhow!("the transform {:?} is not invertible", matrix.rows()))?;
+
+        Ok(Self { matrix, inverse })
+    }
+
+    pub const fn translate(offset: Vec3) -> Self {
+        Self {
+            matrix: Mat4::translation(offset),
+            inverse: Mat4::translation(Vec3::new(-offset.x(), -offset.y(), -offset.z())),
+        }
+    }
+
+    /// Scaling by `factors` along the axes, which must all be non-zero.
+    pub fn scale(factors: Vec3) -> Self {
+        Self {
+            matrix: Mat4::scaling(factors),
+            inverse: Mat4::scaling(Vec3::new(
+                1. / factors.x(),
+                1. / factors.y(),
+                1. / factors.z(),
+            )),
+        }
+    }
+
+    /// See [`Mat4::rotation`].
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let matrix = Mat4::rotation(axis, degrees);
+
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        Self {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub const fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub const fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transforms a surface normal with the inverse transpose, so that it stays
+    /// perpendicular to the transformed surface. The result isn't normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse.m;
+        let column = |i: usize| m[0][i] * n.x() + m[1][i] * n.y() + m[2][i] * n.z();
+
+        Vec3::new(column(0), column(1), column(2))
+    }
+
+    /// Box around the transformed corners of `bbox`.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        if bbox.is_empty() {
+            return Aabb::EMPTY;
+        }
+
+        corners(bbox).fold(Aabb::EMPTY, |acc, corner| acc.grow(self.point(corner)))
+    }
+}
+
+/// Transform moving from `start` at time 0 to `end` at time 1, and staying at either end
+/// outside of that interval. In between, the translations, rotations and scales of the two
+/// are interpolated separately, so that objects turn rather than shear on the way.
+#[derive(Debug, Clone, Copy)]
+pub struct AnimatedTransform {
+    start: Transform,
+    end: Transform,
+    /// `start` and `end` split into their parts, `None` when they're the same.
+    parts: Option<[Decomposed; 2]>,
+}
+
+impl Default for AnimatedTransform {
+    fn default() -> Self {
+        Transform::IDENTITY.into()
+    }
+}
+
+impl From<Transform> for AnimatedTransform {
+    fn from(transform: Transform) -> Self {
+        Self {
+            start: transform,
+            end: transform,
+            parts: None,
+        }
+    }
+}
+
+impl AnimatedTransform {
+    /// Times the motion is evaluated at to bound it, see [`AnimatedTransform::bounding_box`].
+    const BOUND_STEPS: u32 = 32;
+
+    pub fn new(start: Transform, end: Transform) -> Self {
+        let parts = (start != end).then(|| {
+            [
+                Decomposed::new(start.matrix()),
+                Decomposed::new(end.matrix()),
+            ]
+        });
+
+        Self { start, end, parts }
+    }
+
+    pub const fn start(&self) -> &Transform {
+        &self.start
+    }
+
+    pub const fn end(&self) -> &Transform {
+        &self.end
+    }
+
+    pub const fn is_animated(&self) -> bool {
+        self.parts.is_some()
+    }
+
+    /// Transform at `time`.
+    pub fn at(&self, time: f64) -> Transform {
+        let Some([start, end]) = &self.parts else {
+            return self.start;
+        };
+
+        match time {
+            t if t <= 0. => self.start,
+            t if t >= 1. => self.end,
+            t => {
+                let matrix = start.lerp(end, t);
+                // A scale going through 0 on the way flattens the object for an instant
+                matrix
+                    .inverse()
+                    .map_or(self.start, |inverse| Transform { matrix, inverse })
+            }
+        }
+    }
+
+    /// Box around `bbox` over the whole motion of the transform.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let Some([start, end]) = &self.parts else {
+            return self.start.bounding_box(bbox);
+        };
+        if bbox.is_empty() {
+            return Aabb::EMPTY;
+        }
+
+        let steps = f64::from(Self::BOUND_STEPS);
+        let swept = (0..=Self::BOUND_STEPS).fold(Aabb::EMPTY, |acc, i| {
+            acc.union(&self.at(f64::from(i) / steps).bounding_box(bbox))
+        });
+
+        // Between two steps the corners move along arcs, which can bulge out of the boxes
+        // at the steps by up to the sagitta of the arc
+        let step_angle = start.rotation.angle_to(&end.rotation) / steps;
+        let radius = corners(bbox)
+            .map(|corner| {
+                let start = start.scale.transform_vector(corner).length();
+                start.max(end.scale.transform_vector(corner).length())
+            })
+            .fold(0., f64::max);
+
+        swept.expand(2. * radius * (1. - (0.5 * step_angle).cos()))
+    }
+}
+
+/// Affine matrix split into a translation, a rotation and what is left, the scale (which
+/// may include a shear), applied in the reverse order.
+#[derive(Debug, Clone, Copy)]
+struct Decomposed {
+    translation: Vec3,
+    rotation: Quaternion,
+    scale: Mat4,
+}
+
+impl Decomposed {
+    fn new(matrix: &Mat4) -> Self {
+        let m = &matrix.m;
+        let translation = Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let mut linear = *matrix;
+        for row in &mut linear.m[..3] {
+            row[3] = 0.;
+        }
+
+        // Polar decomposition: averaging a matrix with its inverse transpose converges to
+        // its rotation
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let Some(inverse) = rotation.inverse() else {
+                break;
+            };
+            let next = mix(&rotation, &inverse.transpose(), 0.5);
+            let change = (0..3)
+                .map(|i| {
+                    (0..3)
+                        .map(|j| (next.m[i][j] - rotation.m[i][j]).abs())
+                        .sum()
+                })
+                .fold(0., f64::max);
+
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+
+        // A mirroring matrix leaves a reflection, which has no quaternion: the scale takes
+        // the mirroring instead
+        if rotation.determinant3() < 0. {
+            for row in &mut rotation.m[..3] {
+                for value in &mut row[..3] {
+                    *value = -*value;
+                }
+            }
+        }
+
+        Self {
+            translation,
+            rotation: Quaternion::from_matrix(&rotation),
+            // The inverse of a rotation is its transpose
+            scale: rotation.transpose() * linear,
+        }
+    }
+
+    /// Matrix in between `self` at `t = 0` and `other` at `t = 1`.
+    fn lerp(&self, other: &Self, t: f64) -> Mat4 {
+        let translation = self.translation + t * (other.translation - self.translation);
+        let rotation = self.rotation.slerp(&other.rotation, t);
+
+        Mat4::translation(translation) * rotation.to_matrix() * mix(&self.scale, &other.scale, t)
+    }
+}
+
+/// Element-wise interpolation of two matrices.
+fn mix(a: &Mat4, b: &Mat4, t: f64) -> Mat4 {
+    let mut m = [[0.; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = a.m[i][j] + t * (b.m[i][j] - a.m[i][j]);
+        }
+    }
+    Mat4::new(m)
+}
+
+/// The eight corners of `bbox`.
+fn corners(bbox: &Aabb) -> impl Iterator<Item = Point3> {
+    let (min, max) = (bbox.min(), bbox.max());
+    (0..8).map(move |i| {
+        Point3::new(
+            if i & 1 != 0 { max.x() } else { min.x() },
+            if i & 2 != 0 { max.y() } else { min.y() },
+            if i & 4 != 0 { max.z() } else { min.z() },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Mat4, b: &Mat4) {
+        for (row_a, row_b) in a.m.iter().zip(&b.m) {
+            for (x, y) in row_a.iter().zip(row_b) {
+                assert!((x - y).abs() < 1e-9, "{a:?} != {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let affine = Mat4::translation(Vec3::new(1., -2., 3.))
+            * Mat4::rotation(Vec3::new(1., 2., 3.), 40.)
+            * Mat4::scaling(Vec3::new(2., -0.5, 3.));
+        // A zero on the diagonal needs the rows to be swapped
+        let projective = Mat4::new([
+            [0., 2., 0., 1.],
+            [1., 0., 0., 0.],
+            [0., 0., 3., 0.],
+            [0., 0., 1., 1.],
+        ]);
+
+        for m in [affine, projective] {
+            let inverse = m.inverse().expect("the matrix is invertible");
+            assert_close(&(m * inverse), &Mat4::IDENTITY);
+            assert_close(&(inverse * m), &Mat4::IDENTITY);
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Mat4::scaling(Vec3::new(1., 0., 1.)).inverse().is_none());
+
+        let flat = Mat4::new([
+            [1., 2., 3., 0.],
+            [2., 4., 6., 0.],
+            [0., 1., 1., 0.],
+            [0., 0., 0., 1.],
+        ]);
+        assert!(flat.inverse().is_none());
+    }
+
+    #[test]
+    fn decomposition_recomposes_the_matrix() {
+        let rotate = Mat4::rotation(Vec3::new(-1., 2., 0.5), 130.);
+        let shear = Mat4::new([
+            [1., 0.3, 0., 0.],
+            [0., 1., 0., 0.],
+            [0.2, 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ]);
+        let mirror = Mat4::scaling(Vec3::new(1., -2., 1.5));
+
+        for linear in [
+            rotate,
+            rotate * shear,
+            rotate * mirror,
+            mirror * shear * rotate,
+        ] {
+            let m = Mat4::translation(Vec3::new(3., 0., -1.)) * linear;
+            let parts = Decomposed::new(&m);
+
+            assert_close(&parts.lerp(&parts, 0.), &m);
+            // The mirroring, if any, goes to the scale
+            assert_eq!(parts.scale.determinant3() < 0., m.determinant3() < 0.);
+        }
+    }
+
+    #[test]
+    fn motion_stays_inside_its_bounding_box() {
+        let start = Transform::scale(Vec3::new(1., 2., 1.));
+        let end = Transform::rotate(Vec3::new(0., 1., 0.3), 170.)
+            .then(&Transform::scale(Vec3::new(-1.5, 1., 1.)))
+            .then(&Transform::translate(Vec3::new(2., 1., 0.)));
+        let motion = AnimatedTransform::new(start, end);
+        let bbox = Aabb::from_points(Point3::new(-1., -0.5, 0.), Point3::new(2., 1., 3.));
+
+        let bound = motion.bounding_box(&bbox);
+        for i in 0..=1000 {
+            let transform = motion.at(f64::from(i) / 1000.);
+            for corner in corners(&bbox).map(|corner| transform.point(corner)) {
+                for axis in 0..3 {
+                    assert!(
+                        bound.axis(axis).expand(1e-9).contains(corner.axis(axis)),
+                        "{corner:?} at step {i} is outside {bound:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::sync::Arc;
+
+use anyhow::{bail, ensure};
+
+use crate::{
+    Aabb, HitRecord, HitResult, Hittable, HittableList, Interval, Material, Onb, Point3, Ray,
+    Result, SurfaceId, Vec3, INFINITY,
+};
+
+/// Vertex and index buffers shared by all triangles of a mesh.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f64; 2]>,
+    indices: Vec<[usize; 3]>,
+    material: Material,
+    id: SurfaceId,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        indices: Vec<[usize; 3]>,
+        material: Material,
+    ) -> Result<Self> {
+        if let Some(face) = indices
+            .iter()
+            .find(|f| f.iter().any(|&i| i >= positions.len()))
+        {
+            bail!(
+                "face {:?} references a vertex out of range, the mesh has {} vertices",
+                face,
+                positions.len()
+            );
+        }
+
+        Ok(Self {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            indices,
+            material,
+            id: SurfaceId::default(),
+        })
+    }
+
+    /// Parallelogram with corner `q` and edges `u` and `v`, split into two triangles.
+    pub fn quad(q: Point3, u: Vec3, v: Vec3, material: Material) -> Self {
+        Self {
+            positions: vec![q, q + u, q + u + v, q + v],
+            normals: vec![],
+            uvs: vec![[0., 0.], [1., 0.], [1., 1.], [0., 1.]],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            material,
+            id: SurfaceId::default(),
+        }
+    }
+
+    /// Axis-aligned box spanning the two opposite corners `a` and `b`.
+    pub fn cuboid(a: Point3, b: Point3, material: Material) -> Self {
+        let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        let corner = |x: bool, y: bool, z: bool| {
+            Point3::new(
+                if x { max.x() } else { min.x() },
+                if y { max.y() } else { min.y() },
+                if z { max.z() } else { min.z() },
+            )
+        };
+
+        let positions = (0..8)
+            .map(|i| corner(i & 1 != 0, i & 2 != 0, i & 4 != 0))
+            .collect();
+        // Two outward-facing triangles per side: -x, +x, -y, +y, -z, +z
+        let indices = vec![
+            [0, 4, 6],
+            [0, 6, 2],
+            [1, 3, 7],
+            [1, 7, 5],
+            [0, 1, 5],
+            [0, 5, 4],
+            [2, 6, 7],
+            [2, 7, 3],
+            [0, 2, 3],
+            [0, 3, 1],
+            [4, 5, 7],
+            [4, 7, 6],
+        ];
+
+        Self {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            indices,
+            material,
+            id: SurfaceId::default(),
+        }
+    }
+
+    /// Per-vertex normals, interpolated across each face for smooth shading.
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Result<Self> {
+        ensure!(
+            normals.len() == self.positions.len(),
+            "expected {} vertex normals, got {}",
+            self.positions.len(),
+            normals.len()
+        );
+
+        self.normals = normals.iter().map(Vec3::unit).collect();
+        Ok(self)
+    }
+
+    /// Per-vertex texture coordinates. Without them a face is parametrized by its
+    /// barycentric coordinates.
+    pub fn with_uvs(mut self, uvs: Vec<[f64; 2]>) -> Result<Self> {
+        ensure!(
+            uvs.len() == self.positions.len(),
+            "expected {} texture coordinates, got {}",
+            self.positions.len(),
+            uvs.len()
+        );
+
+        self.uvs = uvs;
+        Ok(self)
+    }
+
+    /// Identifiers reported in the object and material ID passes.
+    pub fn with_id(mut self, id: SurfaceId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// One hittable per face, all sharing this mesh's buffers.
+    pub fn into_hittables(self) -> HittableList {
+        let mesh = Arc::new(self);
+
+        (0..mesh.len())
+            .map(|face| Box::new(Triangle::from_mesh(mesh.clone(), face)) as _)
+            .collect()
+    }
+
+    fn vertices(&self, face: usize) -> [Point3; 3] {
+        self.indices[face].map(|i| self.positions[i])
+    }
+
+    fn uvs(&self, face: usize) -> [[f64; 2]; 3] {
+        if self.uvs.is_empty() {
+            [[0., 0.], [1., 0.], [0., 1.]]
+        } else {
+            self.indices[face].map(|i| self.uvs[i])
+        }
+    }
+}
+
+/// Single face of a [`TriangleMesh`].
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Material) -> Self {
+        let mesh = TriangleMesh {
+            positions: vec![a, b, c],
+            normals: vec![],
+            uvs: vec![],
+            indices: vec![[0, 1, 2]],
+            material,
+            id: SurfaceId::default(),
+        };
+
+        Self::from_mesh(Arc::new(mesh), 0)
+    }
+
+    pub const fn from_mesh(mesh: Arc<TriangleMesh>, face: usize) -> Self {
+        Self { mesh, face }
+    }
+
+    /// Solves for the position derivatives from the edges in position and uv space,
+    /// falling back to an arbitrary tangent frame when the uvs are degenerate.
+    fn derivatives([p0, p1, p2]: [Point3; 3], [uv0, uv1, uv2]: [[f64; 2]; 3]) -> (Vec3, Vec3) {
+        let duv02 = [uv0[0] - uv2[0], uv0[1] - uv2[1]];
+        let duv12 = [uv1[0] - uv2[0], uv1[1] - uv2[1]];
+        let dp02 = p0 - p2;
+        let dp12 = p1 - p2;
+
+        let det = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+        if det.abs() < 1e-12 {
+            let frame = Onb::from_w((p1 - p0).cross(p2 - p0));
+            return (frame.u(), frame.v());
+        }
+
+        let inv_det = 1. / det;
+        let dpdu = (duv12[1] * dp02 - duv02[1] * dp12) * inv_det;
+        let dpdv = (duv02[0] * dp12 - duv12[0] * dp02) * inv_det;
+
+        (dpdu, dpdv)
+    }
+}
+
+impl Hittable for Triangle {
+    // Watertight ray/triangle intersection (Woop, Benthin & Wald 2013): vertices are
+    // sheared into a space where the ray runs along +z, so edges shared by two
+    // triangles are evaluated identically and rays can't slip through them.
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        let [p0, p1, p2] = self.mesh.vertices(self.face);
+        let dir = ray.direction();
+
+        let kz = (0..3)
+            .max_by(|&a, &b| dir.axis(a).abs().total_cmp(&dir.axis(b).abs()))
+            .unwrap_or(2);
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if dir.axis(kz) < 0. {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        let sz = 1. / dir.axis(kz);
+        let sx = dir.axis(kx) * sz;
+        let sy = dir.axis(ky) * sz;
+
+        let [a, b, c] = [p0, p1, p2].map(|p| p - ray.origin());
+        let shear = |v: Vec3| (v.axis(kx) - sx * v.axis(kz), v.axis(ky) - sy * v.axis(kz));
+        let (ax, ay) = shear(a);
+        let (bx, by) = shear(b);
+        let (cx, cy) = shear(c);
+
+        let u = cx * by - cy * bx;
+        let v = ax * cy - ay * cx;
+        let w = bx * ay - by * ax;
+
+        if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0. {
+            return None;
+        }
+
+        let t = (u * a.axis(kz) + v * b.axis(kz) + w * c.axis(kz)) * sz / det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let (b0, b1, b2) = (u / det, v / det, w / det);
+        let outward_normal = (p1 - p0).cross(p2 - p0).unit();
+
+        let [uv0, uv1, uv2] = self.mesh.uvs(self.face);
+        let hit_u = b0 * uv0[0] + b1 * uv1[0] + b2 * uv2[0];
+        let hit_v = b0 * uv0[1] + b1 * uv1[1] + b2 * uv2[1];
+        let (dpdu, dpdv) = Self::derivatives([p0, p1, p2], [uv0, uv1, uv2]);
+
+        let mut rec = HitRecord::new(ray.at(t), t, ray, outward_normal, &self.mesh.material)
+            .with_surface_coords(hit_u, hit_v, dpdu, dpdv)
+            .with_id(self.mesh.id)
+            .with_object(self);
+
+        if !self.mesh.normals.is_empty() {
+            let [n0, n1, n2] = self.mesh.indices[self.face].map(|i| self.mesh.normals[i]);
+            rec.set_shading_normal((b0 * n0 + b1 * n1 + b2 * n2).unit());
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2] = self.mesh.vertices(self.face);
+        Aabb::from_points(p0, p1).union(&Aabb::from_points(p2, p2))
+    }
+
+    fn is_light(&self) -> bool {
+        self.mesh.material.is_emissive()
+    }
+
+    // Points are picked uniformly over the area of the triangle
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+        let Some(rec) = self.hit(ray, &Interval::new(0.001, INFINITY)) else {
+            return 0.;
+        };
+
+        let [p0, p1, p2] = self.mesh.vertices(self.face);
+        let normal = (p1 - p0).cross(p2 - p0);
+        let area = 0.5 * normal.length();
+        let direction = ray.direction();
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = direction.unit().dot(normal.unit()).abs();
+
+        if cosine * area <= 0. {
+            return 0.;
+        }
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Point3, _time: f64, (u, v): (f64, f64)) -> Vec3 {
+        let [p0, p1, p2] = self.mesh.vertices(self.face);
+        let su = u.sqrt();
+        let (b0, b1) = (1. - su, v * su);
+
+        b0 * p0 + b1 * p1 + (1. - b0 - b1) * p2 - origin
+    }
+}
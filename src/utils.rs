@@ -3,34 +3,43 @@ use indicatif::{ProgressBar, ProgressStyle};
 
 use crate::{Color, Interval};
 
-pub const INFINITY: f64 = std::f64::INFINITY;
+pub const INFINITY: f64 = f64::INFINITY;
 pub const PI: f64 = std::f64::consts::PI;
 
-pub fn linear_to_gamma(linear_cmp: f64) -> f64 {
-    linear_cmp.sqrt()
+/// sRGB transfer function, encodes a linear channel in [0, 1] for display.
+pub fn linear_to_srgb(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1. / 2.4) - 0.055
+    }
 }
 
-pub fn write_color(color: Color, samples_per_pixel: i64) -> Rgb<u8> {
-    let mut r = color.x();
-    let mut g = color.y();
-    let mut b = color.z();
-
-    let scale = 1. / samples_per_pixel as f64;
-    r *= scale;
-    g *= scale;
-    b *= scale;
-
-    r = linear_to_gamma(r);
-    g = linear_to_gamma(g);
-    b = linear_to_gamma(b);
+/// Inverse of [`linear_to_srgb`].
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
 
-    let intensity = Interval::new(0., 0.999);
-    const RGB: f64 = 256.;
-    let x = (RGB * intensity.clamp(r)) as i64;
-    let y = (RGB * intensity.clamp(g)) as i64;
-    let z = (RGB * intensity.clamp(b)) as i64;
+/// Encodes a display-referred linear colour to sRGB and quantizes it to 8 bits per
+/// channel, clamping everything outside [0, 1].
+pub fn write_color(color: Color) -> Rgb<u8> {
+    let unit = Interval::new(0., 1.);
+    let quantize = |c: f64| (255. * linear_to_srgb(unit.clamp(c)) + 0.5) as u8;
+
+    Rgb([
+        quantize(color.x()),
+        quantize(color.y()),
+        quantize(color.z()),
+    ])
+}
 
-    Rgb([x as u8, y as u8, z as u8])
+/// Relative luminance of a linear sRGB colour.
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
 }
 
 pub fn progress_bar(width: i64, height: i64) -> ProgressBar {
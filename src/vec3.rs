@@ -2,14 +2,29 @@ use derive_more::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubA
 use image::Rgb;
 use rand::{
     distributions::{Distribution, Standard},
-    rngs::ThreadRng,
     Rng,
 };
+use serde::Deserialize;
 use std::{fmt::Display, ops};
 
+use crate::PI;
+
 #[derive(
-    Debug, Clone, Copy, Add, AddAssign, Sub, SubAssign, MulAssign, Mul, Div, DivAssign, Neg,
+    Debug,
+    Clone,
+    Copy,
+    Add,
+    AddAssign,
+    Sub,
+    SubAssign,
+    MulAssign,
+    Mul,
+    Div,
+    DivAssign,
+    Neg,
+    Deserialize,
 )]
+#[serde(from = "[f64; 3]")]
 pub struct Vec3 {
     x: f64,
     y: f64,
@@ -47,6 +62,14 @@ impl Vec3 {
         self.z
     }
 
+    pub const fn axis(&self, n: usize) -> f64 {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
     pub fn r(&self) -> u8 {
         Self::as_u8(u8::MAX as f64 * self.x)
     }
@@ -83,6 +106,10 @@ impl Vec3 {
         )
     }
 
+    pub fn max_component(&self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
+
     pub fn unit(&self) -> Self {
         *self / self.length()
     }
@@ -96,11 +123,11 @@ impl Vec3 {
         Rgb([self.r(), self.g(), self.b()])
     }
 
-    pub fn gen(rng: &mut ThreadRng) -> Self {
+    pub fn gen<R: Rng + ?Sized>(rng: &mut R) -> Self {
         rng.gen()
     }
 
-    pub fn gen_range(rng: &mut ThreadRng, min: f64, max: f64) -> Self {
+    pub fn gen_range<R: Rng + ?Sized>(rng: &mut R, min: f64, max: f64) -> Self {
         Vec3::new(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
@@ -108,35 +135,35 @@ impl Vec3 {
         )
     }
 
-    pub fn random_in_unit_disk(rng: &mut ThreadRng) -> Self {
-        let min = -1.;
-        let max = 1.;
-
-        loop {
-            let p = Vec3::new(rng.gen_range(min..max), rng.gen_range(min..max), 0.);
-
-            if p.length_squared() < 1. {
-                break p;
-            }
+    /// Point in the unit disk of the XY plane from a uniform point `u` of the unit square,
+    /// with Shirley and Chiu's concentric mapping, which keeps strata compact.
+    pub fn sample_unit_disk((u, v): (f64, f64)) -> Self {
+        let (a, b) = (2. * u - 1., 2. * v - 1.);
+        if a == 0. && b == 0. {
+            return Vec3::ZERO;
         }
-    }
 
-    pub fn random_in_unit_sphere(rng: &mut ThreadRng) -> Vec3 {
-        loop {
-            let p = Self::gen_range(rng, -1., 1.);
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, PI / 4. * (b / a))
+        } else {
+            (b, PI / 2. - PI / 4. * (a / b))
+        };
 
-            if p.length_squared() < 1. {
-                break p;
-            }
-        }
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.)
     }
 
-    pub fn random_unit(rng: &mut ThreadRng) -> Vec3 {
-        Self::random_in_unit_sphere(rng).unit()
+    /// Uniformly distributed unit vector from a uniform point `u` of the unit square.
+    pub fn sample_unit_sphere((u, v): (f64, f64)) -> Vec3 {
+        let z = 1. - 2. * u;
+        let r = (1. - z * z).max(0.).sqrt();
+        let phi = 2. * PI * v;
+
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
     }
 
-    pub fn random_in_hemisphere(rng: &mut ThreadRng, normal: Vec3) -> Vec3 {
-        let on_unit_sphere = Self::random_unit(rng);
+    /// Uniformly distributed unit vector on the side of `normal`.
+    pub fn sample_hemisphere(u: (f64, f64), normal: Vec3) -> Vec3 {
+        let on_unit_sphere = Self::sample_unit_sphere(u);
 
         match on_unit_sphere.dot(normal) {
             dot if dot > 0. => on_unit_sphere,
@@ -180,6 +207,12 @@ impl ops::Div<Vec3> for f64 {
     }
 }
 
+impl From<[f64; 3]> for Vec3 {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Self::new(x, y, z)
+    }
+}
+
 impl Distribution<Vec3> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec3 {
         Vec3::new(rng.gen(), rng.gen(), rng.gen())
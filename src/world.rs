@@ -1,28 +1,215 @@
-use crate::{Color, Hittable, HittableList, HittableObj, Material, Point3, Sphere, Vec3};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    load_obj, Aabb, Bvh, Color, Fog, HitRecord, HitResult, Hittable, HittableList, Instance,
+    Interval, Material, Point3, Ray, Result, Sampler, Sphere, SurfaceId, Transform, TraversalStats,
+    Vec3,
+};
 use rand::{thread_rng, Rng};
 
-pub struct World(HittableList);
+/// Objects of a scene, as a two-level acceleration structure: a top-level BVH over
+/// [`Instance`]s, each pointing at its own object, usually a bottom-level [`Bvh`] of a
+/// mesh. Moving objects only rebuilds the top level, see [`World::update_instances`].
+pub struct World {
+    bvh: Bvh<Instance>,
+    /// Indices of the instances of `bvh` that give off light.
+    lights: Vec<usize>,
+    /// Indices of the instances of `bvh` that are participating media.
+    media: Vec<usize>,
+    fog: Option<Fog>,
+}
 
 impl World {
-    pub const fn new(hittables: HittableList) -> Self {
-        Self(hittables)
+    /// World of `hittables` placed as they are, each one its own instance.
+    pub fn new(hittables: HittableList) -> Self {
+        Self::from_instances(
+            hittables
+                .into_iter()
+                .map(|hittable| Instance::new(hittable.into(), Transform::IDENTITY))
+                .collect(),
+        )
+    }
+
+    /// Builds the top-level BVH over `instances`.
+    pub fn from_instances(instances: Vec<Instance>) -> Self {
+        let bvh = Bvh::new(instances);
+        let lights = (0..bvh.len())
+            .filter(|&i| bvh.objects()[i].is_light())
+            .collect();
+        let media = (0..bvh.len())
+            .filter(|&i| bvh.objects()[i].is_medium())
+            .collect();
+
+        Self {
+            bvh,
+            lights,
+            media,
+            fog: None,
+        }
+    }
+
+    /// Fills the space between the objects, within the fog's ball, with `fog`.
+    pub fn with_fog(mut self, fog: Fog) -> Self {
+        self.fog = Some(fog);
+        self
+    }
+
+    pub const fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+
+    /// World made of the meshes in a Wavefront `.obj` file, see [`load_obj`], as one
+    /// instance of a BVH over all of them.
+    pub fn from_obj(path: impl AsRef<Path>) -> Result<Self> {
+        let mesh = Arc::new(Bvh::new(load_obj(path)?));
+        Ok(Self::from_instances(vec![Instance::new(
+            mesh,
+            Transform::IDENTITY,
+        )]))
     }
 
     pub fn hittables(self) -> HittableList {
-        self.0
+        self.into_instances()
+            .into_iter()
+            .map(|instance| Box::new(instance) as _)
+            .collect()
+    }
+
+    /// Instances in the order they were given to [`World::from_instances`].
+    pub fn into_instances(self) -> Vec<Instance> {
+        self.bvh.into_objects()
+    }
+
+    /// Lets `update` edit the instances, e.g. move them with [`Instance::set_transform`],
+    /// then rebuilds the top-level BVH over them. The objects they point at are left as
+    /// they are. Instances are in the order they were given to [`World::from_instances`].
+    pub fn update_instances(&mut self, update: impl FnOnce(&mut [Instance])) {
+        let bvh = std::mem::replace(&mut self.bvh, Bvh::new(vec![]));
+        let mut instances = bvh.into_objects();
+        update(&mut instances);
+
+        let fog = self.fog.take();
+        *self = Self::from_instances(instances);
+        self.fog = fog;
+    }
+
+    /// [`Hittable::hit`], along with the work the traversal took.
+    pub fn hit_with_stats(&self, ray: &Ray, ray_t: &Interval) -> (HitResult<'_>, TraversalStats) {
+        let mut stats = TraversalStats::default();
+        let rec = self.bvh.hit_with_stats(ray, ray_t, &mut stats);
+        (rec, stats)
+    }
+
+    /// [`Hittable::hit`] against surfaces alone, skipping the media, which rays don't hit.
+    pub fn hit_surfaces(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        if self.media.is_empty() {
+            return self.bvh.hit(ray, ray_t);
+        }
+        self.bvh
+            .hit_where(ray, ray_t, |instance| !instance.is_medium())
+    }
+
+    /// Nearest point within `ray_t` where a medium scatters `ray`, see
+    /// [`Hittable::sample_scatter`]. Every medium draws its own numbers from `sampler`, so
+    /// overlapping media attenuate the ray together. The fog is left to the caller.
+    pub fn sample_media(
+        &self,
+        ray: &Ray,
+        ray_t: &Interval,
+        sampler: &mut dyn Sampler,
+    ) -> HitResult<'_> {
+        let mut nearest = None;
+        let mut t_max = ray_t.max();
+        for &i in &self.media {
+            let medium = &self.bvh.objects()[i];
+            if let Some(rec) =
+                medium.sample_scatter(ray, &Interval::new(ray_t.min(), t_max), sampler)
+            {
+                t_max = rec.t;
+                nearest = Some(rec);
+            }
+        }
+
+        nearest
+    }
+
+    /// Fraction of the light travelling along `ray` over `ray_t` that gets through the
+    /// media and the fog, surfaces aside, see [`World::hit_surfaces`]. Media that need
+    /// random numbers to estimate it draw them from `sampler`.
+    pub fn transmittance(&self, ray: &Ray, ray_t: &Interval, sampler: &mut dyn Sampler) -> f64 {
+        let fog = self
+            .fog
+            .as_ref()
+            .map_or(1., |fog| fog.transmittance(ray, ray_t));
+
+        self.media.iter().fold(fog, |transmittance, &i| {
+            transmittance * self.bvh.objects()[i].transmittance(ray, ray_t, sampler)
+        })
+    }
+
+    pub fn light_count(&self) -> usize {
+        self.lights.len()
+    }
+
+    /// Picks a light with `u_light`, then a point on it at `time` with `u`. Returns the
+    /// light, the vector from `origin` to the point and the density of its direction, or
+    /// `None` when there is no light or the point can't be seen from `origin`. Lights are
+    /// picked uniformly.
+    pub fn sample_light(
+        &self,
+        origin: Point3,
+        time: f64,
+        u_light: f64,
+        u: (f64, f64),
+    ) -> Option<(&Instance, Vec3, f64)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let index = ((u_light * self.lights.len() as f64) as usize).min(self.lights.len() - 1);
+        let light = &self.bvh.objects()[self.lights[index]];
+        let direction = light.random(origin, time, u);
+        let pdf = light.pdf_value(&Ray::new(origin, direction, time)) / self.lights.len() as f64;
+
+        (pdf > 0.).then_some((light, direction, pdf))
+    }
+
+    /// Density with which [`World::sample_light`] picks the direction of `ray` from its
+    /// origin, towards the light it hit in `rec`.
+    ///
+    /// Only the light hit first counts, lights behind it along the ray are left out: a
+    /// light sample is only used when it reaches the sampled light itself, see
+    /// [`World::sample_light`], so each light makes up its own part of the light arriving
+    /// from a direction.
+    pub fn light_pdf(&self, rec: &HitRecord, ray: &Ray) -> f64 {
+        match rec.object {
+            Some(object) if object.is_light() && !self.lights.is_empty() => {
+                object.pdf_value(ray) / self.lights.len() as f64
+            }
+            _ => 0.,
+        }
     }
 
+    /// Random spheres scene, different on every call. See [`World::random_scene`] for a
+    /// reproducible one.
     pub fn scene() -> Self {
-        let mut hittables: Vec<Box<dyn Hittable>> = vec![];
+        Self::random_scene(&mut thread_rng())
+    }
 
-        let mat_ground = Material::Lambertian(Color::new(0.5, 0.5, 0.5));
-        hittables.push(Box::new(Sphere::new(
-            Point3::new(0., -1000., 0.),
-            1000.,
-            mat_ground,
-        )));
+    /// Ground, three large spheres and a grid of small spheres with random materials,
+    /// all drawn from `rng`. Every sphere has its own object and material ID.
+    pub fn random_scene<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut hittables: Vec<Box<dyn Hittable>> = vec![];
+        let id = |index: usize| SurfaceId {
+            object: index as u32 + 1,
+            material: index as u32 + 1,
+        };
 
-        let mut rng = thread_rng();
+        let mat_ground = Material::lambertian(Color::new(0.5, 0.5, 0.5));
+        hittables.push(Box::new(
+            Sphere::new(Point3::new(0., -1000., 0.), 1000., mat_ground)
+                .with_id(id(hittables.len())),
+        ));
 
         for a in -11..11 {
             for b in -11..11 {
@@ -36,50 +223,59 @@ impl World {
                 if (center - Point3::new(4., 0.2, 0.)).length() > 0.9 {
                     let mat_spehere = match choose_mat {
                         x if x < 0.8 => {
-                            let albedo: Color = Vec3::gen(&mut rng) * Vec3::gen(&mut rng);
-                            Material::Lambertian(albedo)
+                            let albedo: Color = Vec3::gen(rng) * Vec3::gen(rng);
+                            Material::lambertian(albedo)
                         }
                         x if x < 0.95 => {
-                            let albedo: Color = Vec3::gen_range(&mut rng, 0.5, 1.);
+                            let albedo: Color = Vec3::gen_range(rng, 0.5, 1.);
                             let fuzz = rng.gen::<f64>();
-                            Material::Metal { albedo, fuzz }
+                            Material::metal(albedo, fuzz)
                         }
                         _ => Material::Dialectric(1.5),
                     };
 
-                    hittables.push(Box::new(Sphere::new(center, 0.2, mat_spehere)));
+                    hittables.push(Box::new(
+                        Sphere::new(center, 0.2, mat_spehere).with_id(id(hittables.len())),
+                    ));
                 }
             }
         }
 
-        let mat_2 = Material::Lambertian(Color::new(0.4, 0.2, 0.1));
-        hittables.push(Box::new(Sphere::new(Point3::new(-4., 1., 0.), 1., mat_2)));
+        let mat_2 = Material::lambertian(Color::new(0.4, 0.2, 0.1));
+        hittables.push(Box::new(
+            Sphere::new(Point3::new(-4., 1., 0.), 1., mat_2).with_id(id(hittables.len())),
+        ));
 
         let mat_1 = Material::Dialectric(1.5);
-        hittables.push(Box::new(Sphere::new(Point3::Y, 1., mat_1)));
+        hittables.push(Box::new(
+            Sphere::new(Point3::Y, 1., mat_1).with_id(id(hittables.len())),
+        ));
 
-        let mat_3 = Material::Metal {
-            albedo: Color::new(0.7, 0.6, 0.5),
-            fuzz: 0.,
-        };
-        hittables.push(Box::new(Sphere::new(Point3::new(4., 1., 0.), 1., mat_3)));
+        let mat_3 = Material::metal(Color::new(0.7, 0.6, 0.5), 0.);
+        hittables.push(Box::new(
+            Sphere::new(Point3::new(4., 1., 0.), 1., mat_3).with_id(id(hittables.len())),
+        ));
 
         Self::new(hittables)
     }
 }
 
 impl IntoIterator for World {
-    type Item = HittableObj;
+    type Item = Instance;
 
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+        self.into_instances().into_iter()
     }
 }
 
 impl Hittable for World {
-    fn hit(&self, ray: &crate::Ray, ray_t: &crate::Interval) -> crate::HitResult {
-        self.0.hit(ray, ray_t)
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> HitResult<'_> {
+        self.bvh.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
     }
 }